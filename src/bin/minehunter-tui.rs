@@ -0,0 +1,355 @@
+use std::{
+    env,
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyEventKind,
+        MouseButton, MouseEvent, MouseEventKind,
+    },
+    execute, queue,
+    style::{self, Color, Stylize},
+    terminal,
+};
+use minehunter::engine::{Board, Cell, CellState, MineField, Outcome, Shape};
+
+/// Rows of text above the board.
+const HEADER_HEIGHT: u16 = 2;
+/// Rows of text below the board.
+const FOOTER_HEIGHT: u16 = 2;
+
+enum Game {
+    Waiting(Shape, usize),
+    Playing(Board, Instant),
+    Won(Board, Duration),
+    Lost(Board, Duration),
+}
+
+struct App {
+    game: Game,
+    cursor: (usize, usize),
+    /// First row and column of the board shown, boards larger than the
+    /// terminal scrolling with the cursor.
+    scroll: (usize, usize),
+}
+
+impl Game {
+    fn shape(&self) -> Shape {
+        match self {
+            Self::Waiting(shape, _) => *shape,
+            Self::Playing(board, _) | Self::Won(board, _) | Self::Lost(board, _) => *board.shape(),
+        }
+    }
+
+    fn nmines(&self) -> usize {
+        match self {
+            Self::Waiting(_, nmines) => *nmines,
+            Self::Playing(board, _) | Self::Won(board, _) | Self::Lost(board, _) => board.nmines(),
+        }
+    }
+
+    fn get(&self, irow: usize, icol: usize) -> CellState {
+        match self {
+            Self::Waiting(..) => CellState::Hidden,
+            Self::Playing(board, _) | Self::Won(board, _) | Self::Lost(board, _) => {
+                board.get(irow, icol)
+            }
+        }
+    }
+
+    /// Reveal a hidden cell, or chord on a visible one.
+    fn reveal(&mut self, irow: usize, icol: usize) {
+        if let Self::Waiting(shape, nmines) = self {
+            let field = MineField::with_rand_mines_avoiding(
+                *shape,
+                *nmines,
+                irow,
+                icol,
+                &mut rand::thread_rng(),
+            )
+            .expect("the size of the board is checked by parse_args");
+            *self = Self::Playing(Board::new(field), Instant::now());
+        }
+        if let Self::Playing(board, _) = self {
+            match board.get(irow, icol) {
+                CellState::Hidden | CellState::Questioned => {
+                    board.reveal(irow, icol);
+                }
+                CellState::Visible(_) => board.reveal_around_nb(irow, icol),
+                CellState::Flagged(_) | CellState::AntiFlagged => {}
+            }
+        }
+        self.update_win_lost();
+    }
+
+    fn chord(&mut self, irow: usize, icol: usize) {
+        if let Self::Playing(board, _) = self {
+            board.reveal_around_nb(irow, icol);
+        }
+        self.update_win_lost();
+    }
+
+    fn toggle_flag(&mut self, irow: usize, icol: usize) {
+        if let Self::Playing(board, _) = self {
+            board.toggle_flag(irow, icol);
+        }
+    }
+
+    fn nflagged(&self) -> usize {
+        match self {
+            Self::Waiting(..) => 0,
+            Self::Playing(board, _) | Self::Won(board, _) | Self::Lost(board, _) => {
+                board.nflagged()
+            }
+        }
+    }
+
+    fn elapsed(&self) -> Duration {
+        match self {
+            Self::Waiting(..) => Duration::ZERO,
+            Self::Playing(_, start) => start.elapsed(),
+            Self::Won(_, time) | Self::Lost(_, time) => *time,
+        }
+    }
+
+    fn update_win_lost(&mut self) {
+        if let Self::Playing(board, start) = self {
+            let board = std::mem::take(board);
+            let time = start.elapsed();
+            *self = match board.outcome() {
+                Outcome::Won => Self::Won(board, time),
+                Outcome::Lost => Self::Lost(board, time),
+                Outcome::Ongoing => Self::Playing(board, *start),
+            };
+        }
+    }
+}
+
+fn cell_text(cell: CellState) -> style::StyledContent<&'static str> {
+    const DIGITS: [&str; 9] = ["0", "1", "2", "3", "4", "5", "6", "7", "8"];
+    const COLORS: [Color; 9] = [
+        Color::Reset,
+        Color::Blue,
+        Color::Green,
+        Color::Red,
+        Color::DarkBlue,
+        Color::DarkRed,
+        Color::Cyan,
+        Color::Magenta,
+        Color::Grey,
+    ];
+    match cell {
+        CellState::Hidden => "#".dark_grey(),
+        CellState::Flagged(_) => "F".yellow().bold(),
+        CellState::AntiFlagged => "A".cyan().bold(),
+        CellState::Questioned => "?".magenta(),
+        CellState::Visible(Cell::Mine(_)) => "*".red().bold(),
+        CellState::Visible(Cell::AntiMine) => "-".blue().bold(),
+        CellState::Visible(Cell::Clear) => ".".dark_grey(),
+        CellState::Visible(Cell::Neighbouring(n)) => {
+            // Boards played in the terminal have no anti-mines, so clues are
+            // never negative.
+            let n = usize::from(n.unsigned_abs());
+            DIGITS[n].with(COLORS[n]).bold()
+        }
+    }
+}
+
+/// Number of rows and columns of cells that fit in the terminal.
+fn view_size() -> io::Result<(usize, usize)> {
+    let (width, height) = terminal::size()?;
+    let nrows = height.saturating_sub(HEADER_HEIGHT + FOOTER_HEIGHT).max(1);
+    let ncols = (width / 2).max(1);
+    Ok((usize::from(nrows), usize::from(ncols)))
+}
+
+/// Terminal coordinate of a row or column of the view, which fits in the
+/// terminal.
+fn screen(n: usize) -> u16 {
+    u16::try_from(n).unwrap_or(u16::MAX)
+}
+
+impl App {
+    /// Scroll the view so that the cursor is in it, returning its size.
+    fn scroll_to_cursor(&mut self) -> io::Result<(usize, usize)> {
+        let (nrows, ncols) = view_size()?;
+        let shape = self.game.shape();
+        let follow = |first: usize, cursor: usize, len: usize, total: usize| {
+            first
+                .clamp(cursor.saturating_sub(len - 1), cursor)
+                .min(total.saturating_sub(len))
+        };
+        self.scroll = (
+            follow(self.scroll.0, self.cursor.0, nrows, shape.nrows),
+            follow(self.scroll.1, self.cursor.1, ncols, shape.ncols),
+        );
+        Ok((nrows, ncols))
+    }
+
+    fn draw(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let shape = self.game.shape();
+        let (nrows, ncols) = self.scroll_to_cursor()?;
+        let (first_row, first_col) = self.scroll;
+        let status = match self.game {
+            Game::Waiting(..) => "Pick a cell",
+            Game::Playing(..) => "",
+            Game::Won(..) => "Congratulations!",
+            Game::Lost(..) => "You lost...",
+        };
+        let time = self.game.elapsed();
+        queue!(
+            out,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            style::Print(format!(
+                "Flags: {} / {}   Time: {}.{:01}   {status}",
+                self.game.nflagged(),
+                self.game.nmines(),
+                time.as_secs(),
+                time.subsec_millis() / 100,
+            )),
+        )?;
+        let rows = (first_row..shape.nrows).take(nrows);
+        for (y, irow) in rows.enumerate() {
+            queue!(out, cursor::MoveTo(0, HEADER_HEIGHT + screen(y)))?;
+            for icol in (first_col..shape.ncols).take(ncols) {
+                let text = cell_text(self.game.get(irow, icol));
+                let text = if (irow, icol) == self.cursor {
+                    text.reverse()
+                } else {
+                    text
+                };
+                queue!(out, style::PrintStyledContent(text), style::Print(" "))?;
+            }
+        }
+        queue!(
+            out,
+            cursor::MoveTo(0, HEADER_HEIGHT + screen(nrows.min(shape.nrows)) + 1),
+            style::Print(
+                "arrows/hjkl: move  space: reveal/chord  f: flag  c: chord  r: restart  q: quit"
+            ),
+        )?;
+        out.flush()
+    }
+
+    fn move_cursor(&mut self, drow: isize, dcol: isize) {
+        let shape = self.game.shape();
+        let (irow, icol) = self.cursor;
+        self.cursor = (
+            irow.saturating_add_signed(drow).min(shape.nrows - 1),
+            icol.saturating_add_signed(dcol).min(shape.ncols - 1),
+        );
+    }
+
+    /// Handle a key press, return whether to quit.
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        let (irow, icol) = self.cursor;
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Up | KeyCode::Char('k') => self.move_cursor(-1, 0),
+            KeyCode::Down | KeyCode::Char('j') => self.move_cursor(1, 0),
+            KeyCode::Left | KeyCode::Char('h') => self.move_cursor(0, -1),
+            KeyCode::Right | KeyCode::Char('l') => self.move_cursor(0, 1),
+            KeyCode::Char(' ') | KeyCode::Enter => self.game.reveal(irow, icol),
+            KeyCode::Char('f') => self.game.toggle_flag(irow, icol),
+            KeyCode::Char('c') => self.game.chord(irow, icol),
+            KeyCode::Char('r') => {
+                self.game = Game::Waiting(self.game.shape(), self.game.nmines());
+            }
+            _ => {}
+        }
+        false
+    }
+
+    fn on_mouse(&mut self, mouse: MouseEvent) {
+        let shape = self.game.shape();
+        let Ok((nrows, ncols)) = view_size() else {
+            return;
+        };
+        let Some(y) = mouse.row.checked_sub(HEADER_HEIGHT).map(usize::from) else {
+            return;
+        };
+        let x = usize::from(mouse.column / 2);
+        let (irow, icol) = (self.scroll.0 + y, self.scroll.1 + x);
+        if y >= nrows || x >= ncols || irow >= shape.nrows || icol >= shape.ncols {
+            return;
+        }
+        self.cursor = (irow, icol);
+        match mouse.kind {
+            MouseEventKind::Down(MouseButton::Left) => self.game.reveal(irow, icol),
+            MouseEventKind::Down(MouseButton::Right) => self.game.toggle_flag(irow, icol),
+            MouseEventKind::Down(MouseButton::Middle) => self.game.chord(irow, icol),
+            _ => {}
+        }
+    }
+}
+
+fn run(app: &mut App, out: &mut impl Write) -> io::Result<()> {
+    loop {
+        app.draw(out)?;
+        if !event::poll(Duration::from_millis(100))? {
+            continue;
+        }
+        match event::read()? {
+            Event::Key(key) if key.kind == KeyEventKind::Press && app.on_key(key) => {
+                return Ok(());
+            }
+            Event::Mouse(mouse) => app.on_mouse(mouse),
+            _ => {}
+        }
+    }
+}
+
+fn parse_args() -> Result<(Shape, usize), String> {
+    let args: Vec<_> = env::args().skip(1).collect();
+    match args.as_slice() {
+        [] => Ok((Shape::square(16, 16), 40)),
+        [nrows, ncols, nmines] => {
+            let parse = |s: &String| s.parse::<usize>().map_err(|e| format!("{s}: {e}"));
+            let shape = Shape::square(parse(nrows)?, parse(ncols)?);
+            let nmines = parse(nmines)?;
+            shape.check().map_err(|e| e.to_string())?;
+            if shape.nrows < 3 || shape.ncols < 3 || nmines > shape.ncells() - 9 {
+                return Err("board too small for this number of mines".to_owned());
+            }
+            Ok((shape, nmines))
+        }
+        _ => Err("usage: minehunter-tui [ROWS COLS MINES]".to_owned()),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let (shape, nmines) = match parse_args() {
+        Ok(config) => config,
+        Err(msg) => {
+            eprintln!("{msg}");
+            std::process::exit(2);
+        }
+    };
+    let mut app = App {
+        game: Game::Waiting(shape, nmines),
+        cursor: (0, 0),
+        scroll: (0, 0),
+    };
+
+    let mut out = io::stdout();
+    terminal::enable_raw_mode()?;
+    execute!(
+        out,
+        terminal::EnterAlternateScreen,
+        EnableMouseCapture,
+        cursor::Hide
+    )?;
+    let result = run(&mut app, &mut out);
+    execute!(
+        out,
+        cursor::Show,
+        DisableMouseCapture,
+        terminal::LeaveAlternateScreen
+    )?;
+    terminal::disable_raw_mode()?;
+    result
+}
@@ -0,0 +1,380 @@
+use eframe::egui::{self, Key, Modifiers, PointerButton, Response};
+use serde::{Deserialize, Serialize};
+
+/// Input that chords on a clue, revealing its neighbours once it is
+/// satisfied.
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum ChordInput {
+    LeftClick,
+    MiddleClick,
+    /// Left and right buttons pressed together, released in any order.
+    BothButtons,
+    ShiftClick,
+    CtrlClick,
+    AltClick,
+}
+
+impl ChordInput {
+    const ALL: [(Self, &'static str); 6] = [
+        (Self::LeftClick, "Left click"),
+        (Self::MiddleClick, "Middle click"),
+        (Self::BothButtons, "Left + right click"),
+        (Self::ShiftClick, "Shift + left click"),
+        (Self::CtrlClick, "Ctrl + left click"),
+        (Self::AltClick, "Alt + left click"),
+    ];
+
+    fn modifier_held(&self, modifiers: egui::Modifiers) -> bool {
+        match self {
+            Self::ShiftClick => modifiers.shift,
+            Self::CtrlClick => modifiers.command,
+            Self::AltClick => modifiers.alt,
+            Self::LeftClick | Self::MiddleClick | Self::BothButtons => false,
+        }
+    }
+}
+
+/// What a click asks for on a cell, depending on the bindings.
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub(crate) enum CellCommand {
+    /// Reveal a hidden cell, or chord on a clue if chording is bound to the
+    /// left click.
+    Open,
+    /// Cycle the flags of a hidden cell, or flag the neighbours of a clue
+    /// if flag chords are enabled.
+    Mark,
+    Chord,
+}
+
+/// Command given with the keyboard.
+#[derive(Copy, Clone)]
+pub(crate) enum KeyCommand {
+    /// Move the cursor by the given number of rows and columns.
+    Move(isize, isize),
+    /// Command on the cell under the cursor.
+    Cell(CellCommand),
+    Restart,
+    /// Switch to one of the preset boards.
+    Preset(usize),
+    NextTheme,
+}
+
+const KEYS: [(Key, KeyCommand); 21] = [
+    (Key::ArrowUp, KeyCommand::Move(-1, 0)),
+    (Key::ArrowDown, KeyCommand::Move(1, 0)),
+    (Key::ArrowLeft, KeyCommand::Move(0, -1)),
+    (Key::ArrowRight, KeyCommand::Move(0, 1)),
+    (Key::W, KeyCommand::Move(-1, 0)),
+    (Key::S, KeyCommand::Move(1, 0)),
+    (Key::A, KeyCommand::Move(0, -1)),
+    (Key::D, KeyCommand::Move(0, 1)),
+    (Key::K, KeyCommand::Move(-1, 0)),
+    (Key::J, KeyCommand::Move(1, 0)),
+    (Key::H, KeyCommand::Move(0, -1)),
+    (Key::L, KeyCommand::Move(0, 1)),
+    (Key::Space, KeyCommand::Cell(CellCommand::Open)),
+    (Key::Enter, KeyCommand::Cell(CellCommand::Open)),
+    (Key::F, KeyCommand::Cell(CellCommand::Mark)),
+    (Key::C, KeyCommand::Cell(CellCommand::Chord)),
+    (Key::R, KeyCommand::Restart),
+    (Key::Num1, KeyCommand::Preset(0)),
+    (Key::Num2, KeyCommand::Preset(1)),
+    (Key::Num3, KeyCommand::Preset(2)),
+    (Key::T, KeyCommand::NextTheme),
+];
+
+const KEY_HELP: [(&str, &str); 8] = [
+    ("Arrows, WASD, HJKL", "Move the cursor"),
+    ("Space, Enter", "Reveal"),
+    ("F", "Flag"),
+    ("C", "Chord"),
+    ("R", "Restart"),
+    ("1, 2, 3", "Preset boards"),
+    ("T", "Next theme"),
+    ("Tab, Escape", "Leave the board"),
+];
+
+/// Commands of the keys pressed during the frame. The keys are consumed, so
+/// that they don't also act on the focused widget.
+pub(crate) fn key_commands(ctx: &egui::Context) -> Vec<KeyCommand> {
+    ctx.input_mut(|i| {
+        KEYS.iter()
+            .filter(|(key, _)| i.consume_key(Modifiers::NONE, *key))
+            .map(|&(_, command)| command)
+            .collect()
+    })
+}
+
+/// Buttons clicked on a cell during the last frame, with the state of the
+/// pointer and modifiers at that time.
+pub(crate) struct Clicks {
+    primary: bool,
+    secondary: bool,
+    middle: bool,
+    primary_down: bool,
+    secondary_down: bool,
+    /// Whether any button was released, on the cell or elsewhere.
+    released: bool,
+    modifiers: egui::Modifiers,
+}
+
+impl Clicks {
+    fn with_buttons(response: &Response, primary: bool, secondary: bool, middle: bool) -> Self {
+        response.ctx.input(|i| Self {
+            primary,
+            secondary,
+            middle,
+            primary_down: i.pointer.button_down(PointerButton::Primary),
+            secondary_down: i.pointer.button_down(PointerButton::Secondary),
+            released: i.pointer.any_released(),
+            modifiers: i.modifiers,
+        })
+    }
+
+    /// Clicks on a widget that doesn't move, drags released over it counting
+    /// as clicks.
+    pub(crate) fn lax(response: &Response) -> Self {
+        let released = |button| response.drag_stopped_by(button) && response.hovered();
+        Self::with_buttons(
+            response,
+            response.clicked() || released(PointerButton::Primary),
+            response.secondary_clicked() || released(PointerButton::Secondary),
+            response.middle_clicked() || released(PointerButton::Middle),
+        )
+    }
+
+    /// Clicks on a widget that is panned by dragging.
+    pub(crate) fn strict(response: &Response) -> Self {
+        Self::with_buttons(
+            response,
+            response.clicked(),
+            response.secondary_clicked(),
+            response.middle_clicked(),
+        )
+    }
+}
+
+/// Mouse bindings of the game board, saved with the app settings.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Bindings {
+    pub(crate) chord: ChordInput,
+    /// Whether marking a satisfied clue flags all its hidden neighbours.
+    pub(crate) flag_chord: bool,
+    /// Set after a chord with both buttons, until the other one is released.
+    #[serde(skip)]
+    both_held: bool,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            chord: ChordInput::LeftClick,
+            flag_chord: false,
+            both_held: false,
+        }
+    }
+}
+
+impl Bindings {
+    /// Command issued by the clicks, if any.
+    pub(crate) fn command(&mut self, clicks: Clicks) -> Option<CellCommand> {
+        if self.both_held {
+            // The release of the second button is not a click of its own.
+            if clicks.primary || clicks.secondary {
+                self.both_held = false;
+                return None;
+            }
+            if !clicks.primary_down && !clicks.secondary_down && !clicks.released {
+                self.both_held = false;
+            }
+        }
+        match self.chord {
+            ChordInput::MiddleClick if clicks.middle => return Some(CellCommand::Chord),
+            ChordInput::BothButtons
+                if (clicks.primary && clicks.secondary_down)
+                    || (clicks.secondary && clicks.primary_down) =>
+            {
+                self.both_held = true;
+                return Some(CellCommand::Chord);
+            }
+            chord if clicks.primary && chord.modifier_held(clicks.modifiers) => {
+                return Some(CellCommand::Chord);
+            }
+            _ => {}
+        }
+        if clicks.primary {
+            Some(CellCommand::Open)
+        } else if clicks.secondary {
+            Some(CellCommand::Mark)
+        } else {
+            None
+        }
+    }
+
+    /// Whether [`CellCommand::Open`] chords on clues.
+    pub(crate) fn open_chords(&self) -> bool {
+        self.chord == ChordInput::LeftClick
+    }
+
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.label("Left click reveals a cell, right click flags it.");
+        ui.add_space(5.0);
+        ui.label("Chord on a number with");
+        for (chord, label) in ChordInput::ALL {
+            ui.radio_value(&mut self.chord, chord, label);
+        }
+        ui.add_space(5.0);
+        ui.checkbox(
+            &mut self.flag_chord,
+            "Right click on a satisfied number flags its hidden neighbours",
+        );
+        ui.separator();
+        egui::Grid::new("keys").show(ui, |ui| {
+            for (keys, action) in KEY_HELP {
+                ui.label(keys);
+                ui.label(action);
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clicks with no button pressed, held or released.
+    fn idle() -> Clicks {
+        Clicks {
+            primary: false,
+            secondary: false,
+            middle: false,
+            primary_down: false,
+            secondary_down: false,
+            released: false,
+            modifiers: Modifiers::NONE,
+        }
+    }
+
+    fn bindings(chord: ChordInput) -> Bindings {
+        Bindings {
+            chord,
+            ..Bindings::default()
+        }
+    }
+
+    #[test]
+    fn both_buttons_chord() {
+        // Left released first, the right one still held.
+        let mut b = bindings(ChordInput::BothButtons);
+        let left_up = Clicks {
+            primary: true,
+            secondary_down: true,
+            released: true,
+            ..idle()
+        };
+        assert!(matches!(b.command(left_up), Some(CellCommand::Chord)));
+        // Its release over the same cell is not a click of its own, even
+        // when the other cells see the release first in the same frame.
+        let released_elsewhere = Clicks {
+            released: true,
+            ..idle()
+        };
+        assert!(b.command(released_elsewhere).is_none());
+        let right_up = Clicks {
+            secondary: true,
+            released: true,
+            ..idle()
+        };
+        assert!(b.command(right_up).is_none());
+        assert!(b.command(idle()).is_none());
+
+        // Right released first, the left one then released over another
+        // cell, which sees no click.
+        let right_up = Clicks {
+            secondary: true,
+            primary_down: true,
+            released: true,
+            ..idle()
+        };
+        assert!(matches!(b.command(right_up), Some(CellCommand::Chord)));
+        let other_cell = Clicks {
+            released: true,
+            ..idle()
+        };
+        assert!(b.command(other_cell).is_none());
+        // Clicks after both buttons are up act again.
+        assert!(b.command(idle()).is_none());
+        let left = Clicks {
+            primary: true,
+            released: true,
+            ..idle()
+        };
+        assert!(matches!(b.command(left), Some(CellCommand::Open)));
+    }
+
+    #[test]
+    fn single_buttons_with_both_buttons_chord() {
+        let mut b = bindings(ChordInput::BothButtons);
+        let left = Clicks {
+            primary: true,
+            released: true,
+            ..idle()
+        };
+        assert!(matches!(b.command(left), Some(CellCommand::Open)));
+        let right = Clicks {
+            secondary: true,
+            released: true,
+            ..idle()
+        };
+        assert!(matches!(b.command(right), Some(CellCommand::Mark)));
+    }
+
+    #[test]
+    fn modifier_chords() {
+        let shift = Modifiers {
+            shift: true,
+            ..Modifiers::NONE
+        };
+        let ctrl = Modifiers {
+            command: true,
+            ..Modifiers::NONE
+        };
+        let click = |modifiers| Clicks {
+            primary: true,
+            modifiers,
+            ..idle()
+        };
+        let mut b = bindings(ChordInput::ShiftClick);
+        assert!(matches!(b.command(click(shift)), Some(CellCommand::Chord)));
+        assert!(matches!(b.command(click(ctrl)), Some(CellCommand::Open)));
+        assert!(matches!(
+            b.command(click(Modifiers::NONE)),
+            Some(CellCommand::Open)
+        ));
+        let mut b = bindings(ChordInput::CtrlClick);
+        assert!(matches!(b.command(click(ctrl)), Some(CellCommand::Chord)));
+        assert!(matches!(b.command(click(shift)), Some(CellCommand::Open)));
+        let mut b = bindings(ChordInput::AltClick);
+        let alt = Modifiers {
+            alt: true,
+            ..Modifiers::NONE
+        };
+        assert!(matches!(b.command(click(alt)), Some(CellCommand::Chord)));
+        assert!(!b.open_chords());
+    }
+
+    #[test]
+    fn middle_click_chord() {
+        let middle = || Clicks {
+            middle: true,
+            ..idle()
+        };
+        let mut b = bindings(ChordInput::MiddleClick);
+        assert!(matches!(b.command(middle()), Some(CellCommand::Chord)));
+        let mut b = bindings(ChordInput::LeftClick);
+        assert!(b.command(middle()).is_none());
+        assert!(b.open_chords());
+    }
+}
@@ -0,0 +1,110 @@
+use eframe::{
+    egui::{self, RichText},
+    epaint::{Pos2, Rect, Vec2},
+};
+
+use crate::{
+    bindings::{Bindings, CellCommand, Clicks},
+    engine::{
+        endless::{EndlessBoard, EndlessField},
+        CellState,
+    },
+    ui_objs::{CellButton, ColorTheme},
+};
+
+/// Mines in each chunk of 256 cells.
+const MINES_PER_CHUNK: usize = 40;
+
+/// Endless game, played on a board without edges that is panned by dragging.
+pub(crate) struct EndlessView {
+    board: EndlessBoard,
+    /// Offset of the origin cell from the center of the view.
+    pan: Vec2,
+}
+
+impl EndlessView {
+    pub(crate) fn new() -> Self {
+        let field = EndlessField::new(rand::random(), MINES_PER_CHUNK)
+            .expect("chunks have room for the mines");
+        Self {
+            board: EndlessBoard::new(field),
+            pan: Vec2::ZERO,
+        }
+    }
+
+    pub(crate) fn score(&self) -> usize {
+        self.board.score()
+    }
+
+    /// Draw the part of the board in view, revealing and flagging cells on
+    /// clicks and panning on drags.
+    pub(crate) fn ui(&mut self, ui: &mut egui::Ui, theme: ColorTheme, bindings: &mut Bindings) {
+        let (rect, response) =
+            ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
+        if response.dragged() {
+            self.pan += response.drag_delta();
+        }
+        let size = CellButton::base_size(ui);
+        let pitch = size + 2.0;
+        let origin = rect.center() + self.pan;
+        let cell_at = |pos: Pos2| {
+            let offset = (pos - origin) / pitch;
+            (offset.y.floor() as i64, offset.x.floor() as i64)
+        };
+        let hovered = response.hover_pos().map(cell_at);
+        let command = bindings.command(Clicks::strict(&response));
+        if let (Some((irow, icol)), Some(command)) = (hovered, command) {
+            match (self.board.get(irow, icol), command) {
+                (CellState::Hidden | CellState::Questioned, CellCommand::Open) => {
+                    self.board.reveal(irow, icol);
+                }
+                (CellState::Visible(_), CellCommand::Chord) => {
+                    self.board.reveal_around_nb(irow, icol)
+                }
+                (CellState::Visible(_), CellCommand::Open) if bindings.open_chords() => {
+                    self.board.reveal_around_nb(irow, icol)
+                }
+                (CellState::Visible(_), CellCommand::Mark) if bindings.flag_chord => {
+                    self.board.flag_around_nb(irow, icol)
+                }
+                (_, CellCommand::Mark) => self.board.toggle_flag(irow, icol),
+                _ => {}
+            }
+        }
+        let painter = ui.painter_at(rect);
+        let (row_min, col_min) = cell_at(rect.min);
+        let (row_max, col_max) = cell_at(rect.max);
+        for irow in row_min..=row_max {
+            for icol in col_min..=col_max {
+                let min = origin + Vec2::new(icol as f32, irow as f32) * pitch;
+                CellButton::new(self.board.get(irow, icol), 1.0, theme).paint_square(
+                    &painter,
+                    Rect::from_min_size(min, Vec2::splat(size)),
+                    hovered == Some((irow, icol)),
+                );
+            }
+        }
+    }
+
+    /// Draw the score and controls, return whether endless mode should close.
+    pub(crate) fn controls(&mut self, ui: &mut egui::Ui, best: usize) -> bool {
+        ui.label(RichText::new("Endless").size(20.0));
+        if self.board.is_lost() {
+            ui.label(format!("Game over, {} cells cleared", self.score()));
+        } else {
+            ui.label(format!("Cleared: {}", self.score()));
+        }
+        ui.label(format!("Best: {best}"));
+        let mut close = false;
+        ui.horizontal(|ui| {
+            if ui.button("New game").clicked() {
+                *self = Self::new();
+            }
+            if ui.button("Center").clicked() {
+                self.pan = Vec2::ZERO;
+            }
+            close = ui.button("Close").clicked();
+        });
+        close
+    }
+}
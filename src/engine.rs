@@ -1,158 +1,856 @@
-use std::collections::BTreeSet;
+pub mod code;
+pub mod endless;
+pub mod history;
+pub mod kernel;
+mod metrics;
+pub mod probability;
+pub mod replay;
+pub mod solver;
+pub mod text;
 
-use rand::seq::IteratorRandom;
+use std::{
+    collections::{BTreeSet, VecDeque},
+    fmt,
+};
 
-#[derive(Copy, Clone, Debug)]
+use rand::{seq::IteratorRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use kernel::Kernel;
+
+/// Invalid input given to the engine.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The shape has no rows, columns or layers.
+    EmptyShape,
+    /// Cells of the shape can't hold this many mines, the limit being 3.
+    InvalidMinesPerCell(u8),
+    /// Wrap-around boards must be larger than their kernel in both
+    /// dimensions.
+    TooSmallToWrap,
+    /// Coordinates outside the board.
+    OutOfBounds(usize, usize),
+    /// More mines placed in the cell than it can hold.
+    CellOverfilled(usize, usize),
+    /// Anti-mine placed in a cell holding mines.
+    AntiMineOnMine(usize, usize),
+    /// More mines or anti-mines asked for than there are free places.
+    TooManyMines { requested: usize, available: usize },
+    /// The shape has more cells than [`MAX_CELLS`].
+    TooLarge,
+    /// Saved cells or cell states that don't match the shape.
+    CellCount { expected: usize, found: usize },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::EmptyShape => f.write_str("board without cells"),
+            Self::InvalidMinesPerCell(n) => write!(f, "cells can't hold {n} mines"),
+            Self::TooSmallToWrap => f.write_str("board too small to wrap around"),
+            Self::OutOfBounds(irow, icol) => write!(f, "cell ({irow}, {icol}) out of the board"),
+            Self::CellOverfilled(irow, icol) => write!(f, "too many mines in ({irow}, {icol})"),
+            Self::AntiMineOnMine(irow, icol) => {
+                write!(f, "anti-mine on a mine in ({irow}, {icol})")
+            }
+            Self::TooManyMines {
+                requested,
+                available,
+            } => write!(f, "{requested} mines asked for, room for {available}"),
+            Self::TooLarge => write!(f, "boards are limited to {MAX_CELLS} cells"),
+            Self::CellCount { expected, found } => {
+                write!(f, "{found} cells saved for a board of {expected}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum Cell {
     Clear,
-    Neighbouring(u8),
-    Mine,
+    /// Total number of mines in the neighbouring cells, minus the number of
+    /// anti-mines. It can be zero or negative next to anti-mines.
+    Neighbouring(i16),
+    /// Number of mines in the cell, only above 1 when the shape allows
+    /// several mines per cell.
+    Mine(u8),
+    /// Cell that counts as -1 in the clues around it. Revealing it loses the
+    /// game as a mine does.
+    AntiMine,
 }
 
-#[derive(Default, Copy, Clone)]
+/// How cells are laid out, which defines their neighbours.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Topology {
+    /// Square cells, with the neighbours given by the kernel of the shape.
+    #[default]
+    Square,
+    /// Hexagonal cells with 6 neighbours, odd rows being shifted right by
+    /// half a cell.
+    Hexagonal,
+    /// Square cells, the board wrapping around its edges. Both dimensions
+    /// must be larger than the kernel, at least 3 for the standard one.
+    Toroidal,
+}
+
+/// Neighbours of hexagonal cells, which depend on the parity of the row.
+const HEX_EVEN_ROW: Kernel =
+    Kernel::from_offsets(&[(-1, -1), (-1, 0), (0, -1), (0, 1), (1, -1), (1, 0)]);
+const HEX_ODD_ROW: Kernel =
+    Kernel::from_offsets(&[(-1, 0), (-1, 1), (0, -1), (0, 1), (1, 0), (1, 1)]);
+
+/// Mines next to the revealed area for each mine moved in one repair of a
+/// no-guess layout.
+const FRONTIER_CELLS_PER_MOVE: usize = 8;
+
+/// Largest number of cells of a board, all layers included, keeping the
+/// counts of cells and mines far from overflowing.
+pub const MAX_CELLS: usize = 1 << 28;
+
+/// Size and layout of a board.
+///
+/// Boards with a `depth` above 1 stack several layers of `nrows` by `ncols`
+/// cells, each cell neighbouring the cells of the layers above and below it.
+/// Rows of the layers follow each other: the cells of layer `l` have row
+/// indices from `l * nrows` to `(l + 1) * nrows`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub struct Shape {
     pub nrows: usize,
     pub ncols: usize,
+    #[serde(default)]
+    pub topology: Topology,
+    #[serde(default = "single_layer")]
+    pub depth: usize,
+    /// Cells counted by clues, ignored by hexagonal boards.
+    #[serde(default)]
+    pub kernel: Kernel,
+    /// Largest number of mines a cell can hold, from 1 to 3.
+    #[serde(default = "single_mine")]
+    pub mines_per_cell: u8,
+    /// Number of anti-mines, placed in addition to the mines.
+    #[serde(default)]
+    pub anti_mines: usize,
+}
+
+fn single_layer() -> usize {
+    1
+}
+
+fn single_mine() -> u8 {
+    1
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self {
+            nrows: 0,
+            ncols: 0,
+            topology: Topology::default(),
+            depth: single_layer(),
+            kernel: Kernel::default(),
+            mines_per_cell: single_mine(),
+            anti_mines: 0,
+        }
+    }
 }
 
 impl Shape {
+    /// Single layer of square cells with the standard kernel.
+    pub fn square(nrows: usize, ncols: usize) -> Self {
+        Self {
+            nrows,
+            ncols,
+            ..Self::default()
+        }
+    }
+
+    /// Number of cells of all the layers, saturating for shapes too large
+    /// to pass [`Shape::check`].
     pub fn ncells(&self) -> usize {
-        self.nrows * self.ncols
+        self.checked_ncells().unwrap_or(usize::MAX)
+    }
+
+    fn checked_ncells(&self) -> Option<usize> {
+        self.depth.checked_mul(self.nrows)?.checked_mul(self.ncols)
+    }
+
+    /// Number of rows of all the layers together, saturating for shapes too
+    /// large to pass [`Shape::check`].
+    pub fn total_rows(&self) -> usize {
+        self.depth.saturating_mul(self.nrows)
+    }
+
+    /// Check that the shape can hold a board.
+    pub fn check(&self) -> Result<(), Error> {
+        let ncells = self.checked_ncells();
+        if ncells == Some(0) {
+            Err(Error::EmptyShape)
+        } else if ncells.is_none_or(|n| n > MAX_CELLS) {
+            Err(Error::TooLarge)
+        } else if !(1..=3).contains(&self.mines_per_cell) {
+            Err(Error::InvalidMinesPerCell(self.mines_per_cell))
+        } else if self.topology == Topology::Toroidal
+            && self.nrows.min(self.ncols) <= 2 * self.kernel.reach()
+        {
+            Err(Error::TooSmallToWrap)
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn contains(&self, irow: usize, icol: usize) -> bool {
+        irow < self.total_rows() && icol < self.ncols
     }
 
     fn idx(&self, irow: usize, icol: usize) -> usize {
-        assert!(irow < self.nrows);
+        assert!(irow < self.total_rows());
         assert!(icol < self.ncols);
         irow * self.ncols + icol
     }
 
+    fn try_idx(&self, irow: usize, icol: usize) -> Result<usize, Error> {
+        if self.contains(irow, icol) {
+            Ok(self.idx(irow, icol))
+        } else {
+            Err(Error::OutOfBounds(irow, icol))
+        }
+    }
+
     pub fn cells(&self) -> impl Iterator<Item = (usize, usize)> {
-        let nrows = self.nrows;
+        let nrows = self.total_rows();
         let ncols = self.ncols;
         (0..nrows).flat_map(move |ir| (0..ncols).map(move |ic| (ir, ic)))
     }
 
+    /// Neighbours of a cell, including the cell itself. Cells outside the
+    /// board have none.
     pub fn neighbours(&self, irow: usize, icol: usize) -> impl Iterator<Item = (usize, usize)> {
-        let row_nbs = irow.saturating_sub(1)..=(irow + 1).min(self.nrows - 1);
-        let col_nbs = icol.saturating_sub(1)..=(icol + 1).min(self.ncols - 1);
-        row_nbs.flat_map(move |ir| col_nbs.clone().map(move |ic| (ir, ic)))
+        let shape = *self;
+        let (layers, row) = if self.contains(irow, icol) {
+            let layer = irow / self.nrows;
+            (
+                layer.saturating_sub(1)..(layer + 2).min(self.depth),
+                irow % self.nrows,
+            )
+        } else {
+            (0..0, 0)
+        };
+        layers.flat_map(move |l| {
+            shape
+                .layer_neighbours(row, icol)
+                .map(move |(ir, ic)| (l * shape.nrows + ir, ic))
+        })
+    }
+
+    /// Neighbours of a cell within a layer, rows being counted from the top
+    /// of the layer.
+    fn layer_neighbours(&self, irow: usize, icol: usize) -> impl Iterator<Item = (usize, usize)> {
+        let kernel = match self.topology {
+            Topology::Square | Topology::Toroidal => self.kernel,
+            Topology::Hexagonal if irow.is_multiple_of(2) => HEX_EVEN_ROW,
+            Topology::Hexagonal => HEX_ODD_ROW,
+        };
+        let nrows = self.nrows;
+        let ncols = self.ncols;
+        let wrap = self.topology == Topology::Toroidal;
+        kernel.offsets().filter_map(move |(drow, dcol)| {
+            if wrap {
+                return Some((
+                    (irow + nrows).checked_add_signed(drow)? % nrows,
+                    (icol + ncols).checked_add_signed(dcol)? % ncols,
+                ));
+            }
+            let ir = irow.checked_add_signed(drow).filter(|&ir| ir < nrows)?;
+            let ic = icol.checked_add_signed(dcol).filter(|&ic| ic < ncols)?;
+            Some((ir, ic))
+        })
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedField")]
 pub struct MineField {
     shape: Shape,
     cells: Vec<Cell>,
     n_mines: usize,
 }
 
+/// Saved [`MineField`], checked and its clues recomputed on load.
+#[derive(Deserialize)]
+struct SavedField {
+    shape: Shape,
+    cells: Vec<Cell>,
+}
+
+impl TryFrom<SavedField> for MineField {
+    type Error = Error;
+
+    fn try_from(saved: SavedField) -> Result<Self, Error> {
+        let shape = saved.shape;
+        shape.check()?;
+        check_count(shape.ncells(), saved.cells.len())?;
+        let mut mines = Vec::new();
+        let mut anti_mines = Vec::new();
+        for (cell, content) in shape.cells().zip(saved.cells) {
+            match content {
+                Cell::Mine(n) => mines.extend(std::iter::repeat_n(cell, n.into())),
+                Cell::AntiMine => anti_mines.push(cell),
+                Cell::Clear | Cell::Neighbouring(_) => {}
+            }
+        }
+        Self::with_anti_mines(shape, mines, anti_mines)
+    }
+}
+
+/// Check that as many cells were saved as the shape has.
+fn check_count(expected: usize, found: usize) -> Result<(), Error> {
+    if expected == found {
+        Ok(())
+    } else {
+        Err(Error::CellCount { expected, found })
+    }
+}
+
 impl MineField {
-    pub fn new<T>(nrows: usize, ncols: usize, mines: T) -> Self
+    pub fn new<T>(nrows: usize, ncols: usize, mines: T) -> Result<Self, Error>
     where
         T: IntoIterator<Item = (usize, usize)>,
     {
-        let shape = Shape { nrows, ncols };
-        let mines: BTreeSet<_> = mines.into_iter().collect();
-        let n_mines = mines.len();
-        let mut cells = Vec::with_capacity(shape.ncells());
-        for (irow, icol) in shape.cells() {
-            if mines.contains(&(irow, icol)) {
-                cells.push(Cell::Mine);
-            } else {
-                let n_nb = shape
-                    .neighbours(irow, icol)
-                    .filter(|nb| mines.contains(nb))
-                    .count();
-                cells.push(if n_nb == 0 {
-                    Cell::Clear
-                } else {
-                    Cell::Neighbouring(n_nb as u8)
-                });
-            }
-        }
-        MineField {
+        let shape = Shape::square(nrows, ncols);
+        Self::with_shape(shape, mines)
+    }
+
+    /// Field with the given mines, a cell listed several times holding as
+    /// many mines.
+    pub fn with_shape<T>(shape: Shape, mines: T) -> Result<Self, Error>
+    where
+        T: IntoIterator<Item = (usize, usize)>,
+    {
+        Self::with_anti_mines(shape, mines, [])
+    }
+
+    /// Field with the given mines and anti-mines, the number of anti-mines
+    /// of the shape being set accordingly.
+    pub fn with_anti_mines<T, U>(mut shape: Shape, mines: T, anti_mines: U) -> Result<Self, Error>
+    where
+        T: IntoIterator<Item = (usize, usize)>,
+        U: IntoIterator<Item = (usize, usize)>,
+    {
+        shape.check()?;
+        // Number of mines of each cell, -1 for anti-mines.
+        let mut counts: Vec<Option<i16>> = vec![None; shape.ncells()];
+        for (irow, icol) in mines {
+            let n = counts[shape.try_idx(irow, icol)?].get_or_insert(0);
+            *n += 1;
+            if *n > shape.mines_per_cell.into() {
+                return Err(Error::CellOverfilled(irow, icol));
+            }
+        }
+        let n_mines = counts.iter().flatten().map(|&n| n as usize).sum();
+        shape.anti_mines = 0;
+        for (irow, icol) in anti_mines {
+            let count = &mut counts[shape.try_idx(irow, icol)?];
+            match count {
+                Some(-1) => {}
+                Some(_) => return Err(Error::AntiMineOnMine(irow, icol)),
+                None => {
+                    *count = Some(-1);
+                    shape.anti_mines += 1;
+                }
+            }
+        }
+        let cells = shape
+            .cells()
+            .zip(&counts)
+            .map(|((irow, icol), count)| match *count {
+                Some(-1) => Cell::AntiMine,
+                Some(n) => Cell::Mine(n as u8),
+                None => {
+                    let mut around = shape
+                        .neighbours(irow, icol)
+                        .filter_map(|(ir, ic)| counts[shape.idx(ir, ic)])
+                        .peekable();
+                    if around.peek().is_none() {
+                        Cell::Clear
+                    } else {
+                        Cell::Neighbouring(around.sum())
+                    }
+                }
+            })
+            .collect();
+        Ok(MineField {
             shape,
             cells,
             n_mines,
-        }
+        })
     }
 
-    pub fn with_rand_mines_avoiding(
-        nrows: usize,
-        ncols: usize,
+    /// Field with mines placed at random, away from the neighbours of the
+    /// first click.
+    pub fn with_rand_mines_avoiding<R: Rng + ?Sized>(
+        shape: Shape,
         nmines: usize,
         irow: usize,
         icol: usize,
-    ) -> Self {
-        let shape = Shape { nrows, ncols };
+        rng: &mut R,
+    ) -> Result<Self, Error> {
+        shape.check()?;
+        shape.try_idx(irow, icol)?;
         let mut cells: BTreeSet<_> = shape.cells().collect();
         for nb in shape.neighbours(irow, icol) {
             cells.remove(&nb);
         }
-        let mut rng = ::rand::thread_rng();
-        let mines = cells.into_iter().choose_multiple(&mut rng, nmines);
-        MineField::new(nrows, ncols, mines)
+        // Each cell offers one slot per mine it can hold.
+        let available = cells.len() * usize::from(shape.mines_per_cell);
+        if nmines > available {
+            return Err(Error::TooManyMines {
+                requested: nmines,
+                available,
+            });
+        }
+        let slots = cells
+            .iter()
+            .flat_map(|&cell| std::iter::repeat_n(cell, shape.mines_per_cell.into()));
+        let mines = slots.choose_multiple(rng, nmines);
+        if shape.anti_mines == 0 {
+            return MineField::with_shape(shape, mines);
+        }
+        for mine in &mines {
+            cells.remove(mine);
+        }
+        if shape.anti_mines > cells.len() {
+            return Err(Error::TooManyMines {
+                requested: shape.anti_mines,
+                available: cells.len(),
+            });
+        }
+        let anti_mines = cells.iter().copied().choose_multiple(rng, shape.anti_mines);
+        MineField::with_anti_mines(shape, mines, anti_mines)
+    }
+
+    /// Draw a random layout and repair it until it can be cleared from the
+    /// first click without guessing, returning the layout and whether it
+    /// succeeded.
+    ///
+    /// The layout is played with the solver, and each time the solver is
+    /// stuck a mine next to the revealed area is moved away from it, before
+    /// resuming from where the solver stopped. After `max_repairs` repairs, the
+    /// layout is returned as is. The solver doesn't handle anti-mines, so
+    /// boards with anti-mines are never reported as solvable.
+    pub fn with_rand_mines_no_guess<R: Rng + ?Sized>(
+        shape: Shape,
+        nmines: usize,
+        irow: usize,
+        icol: usize,
+        max_repairs: usize,
+        rng: &mut R,
+    ) -> Result<(Self, bool), Error> {
+        let field = Self::with_rand_mines_avoiding(shape, nmines, irow, icol, rng)?;
+        if shape.anti_mines > 0 {
+            return Ok((field, false));
+        }
+        let mut board = Board::new(field);
+        board.reveal(irow, icol);
+        let mut repairs = 0;
+        loop {
+            solver::play_deductions(&mut board);
+            if matches!(board.outcome(), Outcome::Won) {
+                // Moved mines change clues that earlier deductions relied
+                // on, so the layout is checked again from the first click.
+                let mut replay = Board::new(board.field.clone());
+                replay.reveal(irow, icol);
+                solver::play_deductions(&mut replay);
+                if matches!(replay.outcome(), Outcome::Won) {
+                    return Ok((replay.field, true));
+                }
+                board = replay;
+            }
+            if repairs == max_repairs || !board.move_frontier_mines((irow, icol), rng) {
+                return Ok((board.field, false));
+            }
+            repairs += 1;
+        }
+    }
+
+    /// Mines of the field, cells holding several mines being repeated.
+    fn mines(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.shape
+            .cells()
+            .zip(&self.cells)
+            .flat_map(|(cell, content)| match *content {
+                Cell::Mine(n) => std::iter::repeat_n(cell, n.into()),
+                _ => std::iter::repeat_n(cell, 0),
+            })
     }
 
     pub fn get(&self, irow: usize, icol: usize) -> Cell {
         let icell = self.shape.idx(irow, icol);
         self.cells[icell]
     }
+
+    pub fn try_get(&self, irow: usize, icol: usize) -> Result<Cell, Error> {
+        let icell = self.shape.try_idx(irow, icol)?;
+        Ok(self.cells[icell])
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CellState {
     Hidden,
-    Flagged,
+    /// Number of flags placed on the cell.
+    Flagged(u8),
+    /// Flag marking an anti-mine.
+    AntiFlagged,
+    /// Mark of a cell the player is unsure about, which is otherwise treated
+    /// as hidden.
+    Questioned,
     Visible(Cell),
 }
 
+impl CellState {
+    pub fn nflags(&self) -> usize {
+        match *self {
+            Self::Flagged(n) => n.into(),
+            _ => 0,
+        }
+    }
+
+    /// Whether the cell is hidden, question marks included.
+    pub fn is_hidden(&self) -> bool {
+        matches!(self, Self::Hidden | Self::Questioned)
+    }
+}
+
 pub enum Outcome {
     Won,
     Lost,
     Ongoing,
 }
 
-#[derive(Default)]
+/// Running counts over the cells of a board, kept up to date as cells
+/// change so that queries don't scan the board.
+#[derive(Default, Copy, Clone)]
+struct Counters {
+    /// Cells without mines that are not revealed yet.
+    hidden_safe: usize,
+    flags: usize,
+    anti_flags: usize,
+    /// Revealed mines and anti-mines.
+    revealed_mines: usize,
+}
+
+impl Counters {
+    /// Counts of a single cell.
+    fn of(cell: Cell, state: CellState) -> Self {
+        let mine = matches!(cell, Cell::Mine(_) | Cell::AntiMine);
+        let visible = matches!(state, CellState::Visible(_));
+        Self {
+            hidden_safe: usize::from(!mine && !visible),
+            flags: state.nflags(),
+            anti_flags: usize::from(state == CellState::AntiFlagged),
+            revealed_mines: usize::from(mine && visible),
+        }
+    }
+
+    fn add(&mut self, other: Self) {
+        self.hidden_safe += other.hidden_safe;
+        self.flags += other.flags;
+        self.anti_flags += other.anti_flags;
+        self.revealed_mines += other.revealed_mines;
+    }
+
+    fn sub(&mut self, other: Self) {
+        self.hidden_safe -= other.hidden_safe;
+        self.flags -= other.flags;
+        self.anti_flags -= other.anti_flags;
+        self.revealed_mines -= other.revealed_mines;
+    }
+}
+
+#[derive(Default, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedBoard")]
 pub struct Board {
     field: MineField,
     state: Vec<CellState>,
+    /// Whether flags cycle through a question mark before being removed.
+    question_marks: bool,
+    #[serde(skip)]
+    counters: Counters,
+}
+
+/// Saved [`Board`], checked and its counters recomputed on load.
+#[derive(Deserialize)]
+struct SavedBoard {
+    field: MineField,
+    state: Vec<CellState>,
+    #[serde(default)]
+    question_marks: bool,
+}
+
+impl TryFrom<SavedBoard> for Board {
+    type Error = Error;
+
+    fn try_from(saved: SavedBoard) -> Result<Self, Error> {
+        check_count(saved.field.shape.ncells(), saved.state.len())?;
+        let mut board = Self::with_state(saved.field, saved.state);
+        board.question_marks = saved.question_marks;
+        Ok(board)
+    }
 }
 
 impl Board {
     pub fn new(field: MineField) -> Self {
         let state = vec![CellState::Hidden; field.shape.ncells()];
-        Self { field, state }
+        Self::with_state(field, state)
+    }
+
+    fn with_state(field: MineField, state: Vec<CellState>) -> Self {
+        let mut counters = Counters::default();
+        for (&cell, &state) in field.cells.iter().zip(&state) {
+            counters.add(Counters::of(cell, state));
+        }
+        Self {
+            field,
+            state,
+            question_marks: false,
+            counters,
+        }
+    }
+
+    /// Move mines from hidden cells next to the revealed or flagged area to
+    /// hidden cells away from it, updating the revealed clues and flooding
+    /// the openings they lead to. Returns `false` if no mine can be moved.
+    ///
+    /// Flagged cells are left alone, as are the cells that clues see, so
+    /// that the clues only lose mines. Long frontiers get several mines
+    /// moved at once, one for every [`FRONTIER_CELLS_PER_MOVE`] of them.
+    /// When all the hidden cells are on the frontier, a single mine goes to
+    /// a revealed cell surrounded by revealed cells, away from the first
+    /// click, which is hidden again.
+    fn move_frontier_mines<R: Rng + ?Sized>(
+        &mut self,
+        first_click: (usize, usize),
+        rng: &mut R,
+    ) -> bool {
+        let shape = self.field.shape;
+        let frontier: BTreeSet<_> = shape
+            .cells()
+            .filter(|&(ir, ic)| !self.get(ir, ic).is_hidden())
+            .flat_map(|(ir, ic)| shape.neighbours(ir, ic))
+            .filter(|&(ir, ic)| self.get(ir, ic).is_hidden())
+            .collect();
+        let froms: Vec<_> = frontier
+            .iter()
+            .copied()
+            .filter(|&(ir, ic)| matches!(self.field.get(ir, ic), Cell::Mine(_)))
+            .collect();
+        let nmoves = (froms.len() / FRONTIER_CELLS_PER_MOVE).max(1);
+        let mut tos = shape
+            .cells()
+            .filter(|cell| !frontier.contains(cell))
+            .filter(|&(ir, ic)| self.get(ir, ic) == CellState::Hidden)
+            .filter(|&(ir, ic)| match self.field.get(ir, ic) {
+                Cell::Mine(n) => n < shape.mines_per_cell,
+                _ => true,
+            })
+            .choose_multiple(rng, nmoves);
+        let mut rehidden = None;
+        if tos.is_empty() {
+            let opening: BTreeSet<_> = shape
+                .neighbours(first_click.0, first_click.1)
+                .chain([first_click])
+                .collect();
+            rehidden = shape
+                .cells()
+                .filter(|cell| !opening.contains(cell))
+                .filter(|&(ir, ic)| {
+                    shape
+                        .neighbours(ir, ic)
+                        .all(|(jr, jc)| matches!(self.get(jr, jc), CellState::Visible(_)))
+                })
+                .choose(rng);
+            tos.extend(rehidden);
+        }
+        let froms = froms.into_iter().choose_multiple(rng, tos.len());
+        if froms.is_empty() {
+            return false;
+        }
+        let mut mines: Vec<_> = self.field.mines().collect();
+        for (from, &to) in froms.into_iter().zip(&tos) {
+            let imine = mines.iter().position(|&cell| cell == from).unwrap();
+            mines[imine] = to;
+        }
+        let field = MineField::with_shape(shape, mines).expect("the target cells have room");
+        let state = shape
+            .cells()
+            .zip(&self.state)
+            .zip(&field.cells)
+            .map(|((cell, &state), &content)| match state {
+                _ if Some(cell) == rehidden => CellState::Hidden,
+                CellState::Visible(_) => CellState::Visible(content),
+                state => state,
+            })
+            .collect();
+        let question_marks = self.question_marks;
+        *self = Self::with_state(field, state);
+        self.question_marks = question_marks;
+        // Clues that dropped to zero open their neighbours.
+        let hidden_around_clear: Vec<_> = shape
+            .cells()
+            .filter(|&(ir, ic)| self.get(ir, ic) == CellState::Visible(Cell::Clear))
+            .flat_map(|(ir, ic)| shape.neighbours(ir, ic))
+            .filter(|&(ir, ic)| self.get(ir, ic).is_hidden())
+            .collect();
+        for (ir, ic) in hidden_around_clear {
+            self.reveal(ir, ic);
+        }
+        true
+    }
+
+    pub fn question_marks(&self) -> bool {
+        self.question_marks
+    }
+
+    pub fn set_question_marks(&mut self, enabled: bool) {
+        self.question_marks = enabled;
+    }
+
+    /// Change the state of a cell, updating the counters.
+    fn set(&mut self, icell: usize, state: CellState) {
+        let cell = self.field.cells[icell];
+        self.counters.sub(Counters::of(cell, self.state[icell]));
+        self.counters.add(Counters::of(cell, state));
+        self.state[icell] = state;
     }
 
     pub fn nmines(&self) -> usize {
         self.field.n_mines
     }
 
-    pub fn reveal(&mut self, irow: usize, icol: usize) -> Cell {
-        if let CellState::Visible(cell) = self.get(irow, icol) {
-            cell
-        } else {
-            let shape = &self.field.shape;
-            let icell = shape.idx(irow, icol);
-            let cell = self.field.get(irow, icol);
-            self.state[icell] = CellState::Visible(cell);
+    /// State of all the cells, row by row.
+    pub fn cell_states(&self) -> &[CellState] {
+        &self.state
+    }
+
+    /// Reveal a cell and the openings it leads to, returning the newly
+    /// revealed cells.
+    ///
+    /// Openings are flooded from a queue rather than recursively, so that
+    /// large boards don't overflow the stack.
+    pub fn reveal(&mut self, irow: usize, icol: usize) -> Vec<(usize, usize)> {
+        let shape = self.field.shape;
+        let mut revealed = Vec::new();
+        let mut queue = VecDeque::from([(irow, icol)]);
+        while let Some((ir, ic)) = queue.pop_front() {
+            let icell = shape.idx(ir, ic);
+            if let CellState::Visible(_) = self.state[icell] {
+                continue;
+            }
+            let cell = self.field.cells[icell];
+            self.set(icell, CellState::Visible(cell));
+            revealed.push((ir, ic));
             if matches!(cell, Cell::Clear) {
+                queue.extend(
+                    shape
+                        .neighbours(ir, ic)
+                        .filter(|&(jr, jc)| !matches!(self.get(jr, jc), CellState::Visible(_))),
+                );
+            }
+        }
+        revealed
+    }
+
+    pub fn try_reveal(&mut self, irow: usize, icol: usize) -> Result<Vec<(usize, usize)>, Error> {
+        self.field.shape.try_idx(irow, icol)?;
+        Ok(self.reveal(irow, icol))
+    }
+
+    /// Reveal the hidden neighbours of a clue if the flags around it account
+    /// for its number, anti-mine flags counting as -1.
+    ///
+    /// At least one flag is needed, since clues can be zero next to mines
+    /// and anti-mines.
+    pub fn reveal_around_nb(&mut self, irow: usize, icol: usize) {
+        if let CellState::Visible(Cell::Neighbouring(n_nb)) = self.get(irow, icol) {
+            let shape = self.field.shape;
+            let flags: Vec<_> = shape
+                .neighbours(irow, icol)
+                .map(|(ir, ic)| self.get(ir, ic))
+                .filter(|state| matches!(state, CellState::Flagged(_) | CellState::AntiFlagged))
+                .collect();
+            let total: isize = flags
+                .iter()
+                .map(|state| match state {
+                    CellState::AntiFlagged => -1,
+                    state => state.nflags() as isize,
+                })
+                .sum();
+            if !flags.is_empty() && total == n_nb.into() {
                 for (ir, ic) in shape.neighbours(irow, icol) {
-                    self.reveal(ir, ic);
+                    if self.get(ir, ic).is_hidden() {
+                        self.reveal(ir, ic);
+                    }
                 }
             }
-            cell
         }
     }
 
+    /// Fill the hidden neighbours of a clue with flags if they must all be
+    /// full of mines, the clue missing as many mines as they can still hold.
+    ///
+    /// Boards with anti-mines are left alone, their clues not telling how
+    /// many mines are around.
+    pub fn flag_around_nb(&mut self, irow: usize, icol: usize) {
+        let shape = self.field.shape;
+        let CellState::Visible(Cell::Neighbouring(n_nb)) = self.get(irow, icol) else {
+            return;
+        };
+        if shape.anti_mines > 0 {
+            return;
+        }
+        let capacity = usize::from(shape.mines_per_cell);
+        let mut flagged = 0;
+        let mut room = 0;
+        let mut unfilled = Vec::new();
+        for (ir, ic) in shape.neighbours(irow, icol) {
+            let state = self.get(ir, ic);
+            if matches!(state, CellState::Visible(_)) {
+                continue;
+            }
+            flagged += state.nflags();
+            if state.nflags() < capacity {
+                room += capacity - state.nflags();
+                unfilled.push(shape.idx(ir, ic));
+            }
+        }
+        if room > 0 && flagged + room == n_nb.max(0) as usize {
+            for icell in unfilled {
+                self.set(icell, CellState::Flagged(shape.mines_per_cell));
+            }
+        }
+    }
+
+    /// Add a flag to a hidden cell, cycling back to no flag once the cell
+    /// has as many flags as it can hold mines, through an anti-mine flag if
+    /// the board has anti-mines and a question mark if they are enabled.
     pub fn toggle_flag(&mut self, irow: usize, icol: usize) {
-        let icell = self.field.shape.idx(irow, icol);
-        self.state[icell] = match self.state[icell] {
-            CellState::Hidden => CellState::Flagged,
-            CellState::Flagged => CellState::Hidden,
+        let shape = self.field.shape;
+        let icell = shape.idx(irow, icol);
+        let state = match self.state[icell] {
+            CellState::Hidden => CellState::Flagged(1),
+            CellState::Flagged(n) if n < shape.mines_per_cell => CellState::Flagged(n + 1),
+            CellState::Flagged(_) if shape.anti_mines > 0 => CellState::AntiFlagged,
+            CellState::Flagged(_) | CellState::AntiFlagged if self.question_marks => {
+                CellState::Questioned
+            }
+            CellState::Flagged(_) | CellState::AntiFlagged | CellState::Questioned => {
+                CellState::Hidden
+            }
             other => other,
         };
+        self.set(icell, state);
     }
 
     pub fn get(&self, irow: usize, icol: usize) -> CellState {
@@ -160,32 +858,37 @@ impl Board {
         self.state[icell]
     }
 
+    pub fn try_get(&self, irow: usize, icol: usize) -> Result<CellState, Error> {
+        let icell = self.field.shape.try_idx(irow, icol)?;
+        Ok(self.state[icell])
+    }
+
     pub fn shape(&self) -> &Shape {
         &self.field.shape
     }
 
+    pub fn field(&self) -> &MineField {
+        &self.field
+    }
+
     pub fn outcome(&self) -> Outcome {
-        let n_mines = self.field.n_mines;
-        let mut n_hidden = 0;
-        for (ir, ic) in self.shape().cells() {
-            match self.get(ir, ic) {
-                CellState::Hidden | CellState::Flagged => n_hidden += 1,
-                CellState::Visible(Cell::Mine) => return Outcome::Lost,
-                CellState::Visible(_) => {}
-            }
-        }
-        if n_hidden == n_mines {
+        if self.counters.revealed_mines > 0 {
+            Outcome::Lost
+        } else if self.counters.hidden_safe == 0 {
             Outcome::Won
         } else {
             Outcome::Ongoing
         }
     }
 
+    /// Total number of mine flags, which may be several per cell.
     pub fn nflagged(&self) -> usize {
-        self.shape()
-            .cells()
-            .filter(|&(ir, ic)| matches!(self.get(ir, ic), CellState::Flagged))
-            .count()
+        self.counters.flags
+    }
+
+    /// Number of cells flagged as anti-mines.
+    pub fn n_anti_flagged(&self) -> usize {
+        self.counters.anti_flags
     }
 }
 
@@ -195,11 +898,11 @@ mod tests {
 
     #[test]
     fn new_minefield() {
-        let mf = MineField::new(3, 4, [(1, 2), (0, 0)]);
+        let mf = MineField::new(3, 4, [(1, 2), (0, 0)]).unwrap();
         assert_eq!(mf.shape.nrows, 3);
         assert_eq!(mf.shape.ncols, 4);
-        assert!(matches!(mf.get(1, 2), Cell::Mine));
-        assert!(matches!(mf.get(0, 0), Cell::Mine));
+        assert!(matches!(mf.get(1, 2), Cell::Mine(1)));
+        assert!(matches!(mf.get(0, 0), Cell::Mine(1)));
         assert!(matches!(mf.get(2, 1), Cell::Neighbouring(1)));
         assert!(matches!(mf.get(0, 1), Cell::Neighbouring(2)));
         assert!(matches!(mf.get(1, 1), Cell::Neighbouring(2)));
@@ -207,17 +910,323 @@ mod tests {
         assert!(matches!(mf.get(1, 3), Cell::Neighbouring(1)));
     }
 
+    #[test]
+    fn hexagonal_minefield() {
+        let shape = Shape {
+            nrows: 3,
+            ncols: 3,
+            topology: Topology::Hexagonal,
+            ..Shape::default()
+        };
+        assert_eq!(shape.neighbours(1, 1).count(), 7);
+        assert_eq!(shape.neighbours(0, 0).count(), 3);
+        assert!(shape.neighbours(1, 1).any(|nb| nb == (0, 2)));
+        assert!(!shape.neighbours(1, 1).any(|nb| nb == (0, 0)));
+
+        let mf = MineField::with_shape(shape, [(0, 0), (0, 2), (2, 2)]).unwrap();
+        assert!(matches!(mf.get(1, 1), Cell::Neighbouring(2)));
+        assert!(matches!(mf.get(1, 0), Cell::Neighbouring(1)));
+        assert!(matches!(mf.get(2, 0), Cell::Clear));
+    }
+
+    #[test]
+    fn knight_minefield() {
+        let shape = Shape {
+            nrows: 4,
+            ncols: 4,
+            kernel: Kernel::KNIGHT,
+            ..Shape::default()
+        };
+        let mf = MineField::with_shape(shape, [(0, 0)]).unwrap();
+        assert!(matches!(mf.get(1, 2), Cell::Neighbouring(1)));
+        assert!(matches!(mf.get(2, 1), Cell::Neighbouring(1)));
+        assert!(matches!(mf.get(1, 1), Cell::Clear));
+
+        // Clear cells only reveal the cells a knight move away, which never
+        // reaches the corner opposite to the mine.
+        let mut board = Board::new(mf);
+        board.reveal(1, 1);
+        assert!(matches!(board.get(2, 2), CellState::Visible(Cell::Clear)));
+        assert!(matches!(
+            board.get(2, 1),
+            CellState::Visible(Cell::Neighbouring(1))
+        ));
+        assert!(matches!(board.get(3, 3), CellState::Hidden));
+        board.reveal(3, 3);
+        assert!(matches!(board.outcome(), Outcome::Won));
+    }
+
+    #[test]
+    fn multi_mine_minefield() {
+        let shape = Shape {
+            nrows: 2,
+            ncols: 3,
+            mines_per_cell: 3,
+            ..Shape::default()
+        };
+        let mf = MineField::with_shape(shape, [(0, 0), (0, 0), (0, 2)]).unwrap();
+        assert!(matches!(mf.get(0, 0), Cell::Mine(2)));
+        assert!(matches!(mf.get(0, 1), Cell::Neighbouring(3)));
+        assert!(matches!(mf.get(1, 2), Cell::Neighbouring(1)));
+
+        let mut board = Board::new(mf);
+        assert_eq!(board.nmines(), 3);
+        board.toggle_flag(0, 0);
+        board.toggle_flag(0, 0);
+        board.toggle_flag(0, 2);
+        assert!(matches!(board.get(0, 0), CellState::Flagged(2)));
+        assert_eq!(board.nflagged(), 3);
+        board.toggle_flag(0, 0);
+        board.toggle_flag(0, 0);
+        assert!(matches!(board.get(0, 0), CellState::Hidden));
+
+        for (ir, ic) in [(0, 1), (1, 0), (1, 1)] {
+            board.reveal(ir, ic);
+        }
+        assert!(matches!(board.outcome(), Outcome::Ongoing));
+        board.reveal(1, 2);
+        assert!(matches!(board.outcome(), Outcome::Won));
+    }
+
+    #[test]
+    fn question_marks() {
+        let mut board = Board::new(MineField::new(3, 3, [(0, 0)]).unwrap());
+        board.set_question_marks(true);
+        board.toggle_flag(0, 0);
+        board.toggle_flag(0, 0);
+        assert_eq!(board.get(0, 0), CellState::Questioned);
+        assert_eq!(board.nflagged(), 0);
+        board.toggle_flag(0, 0);
+        assert_eq!(board.get(0, 0), CellState::Hidden);
+
+        // Question marks don't count as flags when chording.
+        board.toggle_flag(0, 1);
+        board.toggle_flag(0, 1);
+        board.reveal(1, 1);
+        board.reveal_around_nb(1, 1);
+        assert_eq!(board.get(0, 1), CellState::Questioned);
+        board.toggle_flag(0, 0);
+        board.reveal_around_nb(1, 1);
+        assert!(matches!(board.outcome(), Outcome::Won));
+
+        board.set_question_marks(false);
+        board.toggle_flag(0, 0);
+        assert_eq!(board.get(0, 0), CellState::Hidden);
+    }
+
+    #[test]
+    fn flag_chord() {
+        let shape = Shape {
+            nrows: 3,
+            ncols: 3,
+            mines_per_cell: 2,
+            ..Shape::default()
+        };
+        let mf = MineField::with_shape(shape, [(0, 0), (0, 0), (0, 1), (0, 1), (0, 2)]).unwrap();
+        let mut board = Board::new(mf);
+        board.reveal(2, 0);
+        // The clue can't tell which cells hold two mines.
+        board.flag_around_nb(1, 1);
+        assert_eq!(board.nflagged(), 0);
+
+        board.flag_around_nb(1, 0);
+        assert_eq!(board.get(0, 0), CellState::Flagged(2));
+        assert_eq!(board.get(0, 1), CellState::Flagged(2));
+        board.toggle_flag(0, 2);
+        board.flag_around_nb(1, 1);
+        assert_eq!(board.get(0, 2), CellState::Flagged(1));
+        assert_eq!(board.nflagged(), 5);
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(MineField::new(0, 3, []).err(), Some(Error::EmptyShape));
+        assert_eq!(
+            MineField::new(3, 3, [(1, 3)]).err(),
+            Some(Error::OutOfBounds(1, 3))
+        );
+        assert_eq!(
+            MineField::new(3, 3, [(1, 1), (1, 1)]).err(),
+            Some(Error::CellOverfilled(1, 1))
+        );
+        let shape = Shape::square(4, 4);
+        assert_eq!(
+            MineField::with_anti_mines(shape, [(0, 0)], [(0, 0)]).err(),
+            Some(Error::AntiMineOnMine(0, 0))
+        );
+        let mut rng = rand::thread_rng();
+        assert!(MineField::with_rand_mines_avoiding(shape, 12, 0, 0, &mut rng).is_ok());
+        assert_eq!(
+            MineField::with_rand_mines_avoiding(shape, 13, 0, 0, &mut rng).err(),
+            Some(Error::TooManyMines {
+                requested: 13,
+                available: 12
+            })
+        );
+        assert!(Shape::default().neighbours(0, 0).next().is_none());
+
+        let mut board = Board::new(MineField::new(3, 3, [(0, 0)]).unwrap());
+        assert_eq!(board.try_get(3, 0).err(), Some(Error::OutOfBounds(3, 0)));
+        assert_eq!(board.try_reveal(2, 2).map(|cells| cells.len()), Ok(8));
+        assert_eq!(
+            board.try_get(1, 1),
+            Ok(CellState::Visible(Cell::Neighbouring(1)))
+        );
+    }
+
+    #[test]
+    fn anti_mine_minefield() {
+        let shape = Shape::square(5, 5);
+        let mf = MineField::with_anti_mines(shape, [(0, 0)], [(0, 2), (2, 2)]).unwrap();
+        assert_eq!(mf.shape.anti_mines, 2);
+        assert!(matches!(mf.get(0, 2), Cell::AntiMine));
+        assert!(matches!(mf.get(0, 1), Cell::Neighbouring(0)));
+        assert!(matches!(mf.get(1, 2), Cell::Neighbouring(-2)));
+        assert!(matches!(mf.get(1, 0), Cell::Neighbouring(1)));
+        assert!(matches!(mf.get(4, 4), Cell::Clear));
+
+        let mut board = Board::new(mf);
+        board.reveal(0, 1);
+        board.toggle_flag(0, 0);
+        board.reveal_around_nb(0, 1);
+        assert!(matches!(board.get(1, 1), CellState::Hidden));
+        board.toggle_flag(0, 2);
+        board.toggle_flag(0, 2);
+        assert!(matches!(board.get(0, 2), CellState::AntiFlagged));
+        assert_eq!(board.n_anti_flagged(), 1);
+        board.reveal_around_nb(0, 1);
+        assert!(matches!(
+            board.get(1, 1),
+            CellState::Visible(Cell::Neighbouring(-1))
+        ));
+        assert!(matches!(board.outcome(), Outcome::Ongoing));
+        board.reveal(2, 2);
+        assert!(matches!(board.outcome(), Outcome::Lost));
+    }
+
+    #[test]
+    fn counters_after_load() {
+        let mut board = Board::new(MineField::new(3, 3, [(0, 0), (2, 2)]).unwrap());
+        board.toggle_flag(0, 0);
+        board.reveal(0, 2);
+        assert_eq!(board.nflagged(), 1);
+        let text = ron::to_string(&board).unwrap();
+        let mut board: Board = ron::from_str(&text).unwrap();
+        assert_eq!(board.nflagged(), 1);
+        for (ir, ic) in [(0, 1), (1, 0), (1, 1), (1, 2), (2, 0)] {
+            assert!(matches!(board.outcome(), Outcome::Ongoing));
+            board.reveal(ir, ic);
+        }
+        assert!(matches!(board.outcome(), Outcome::Won));
+        board.reveal(2, 2);
+        assert!(matches!(board.outcome(), Outcome::Lost));
+    }
+
+    #[test]
+    fn reveal_large_opening() {
+        let mut board = Board::new(MineField::new(1000, 1000, [(500, 500)]).unwrap());
+        let revealed = board.reveal(0, 0);
+        assert_eq!(revealed.len(), 1000 * 1000 - 1);
+        assert_eq!(revealed[0], (0, 0));
+        assert!(matches!(board.outcome(), Outcome::Won));
+        assert!(board.reveal(0, 0).is_empty());
+        assert!(Shape::square(2000, 2000).check().is_ok());
+        assert!(Shape::square(1 << 15, 1 << 15).check().is_err());
+    }
+
+    #[test]
+    fn layered_minefield() {
+        let shape = Shape {
+            nrows: 3,
+            ncols: 3,
+            depth: 3,
+            ..Shape::default()
+        };
+        assert_eq!(shape.ncells(), 27);
+        assert_eq!(shape.neighbours(4, 1).count(), 27);
+        assert_eq!(shape.neighbours(0, 0).count(), 8);
+        let mines = shape.cells().filter(|&cell| cell != (4, 1));
+        let mf = MineField::with_shape(shape, mines).unwrap();
+        assert!(matches!(mf.get(4, 1), Cell::Neighbouring(26)));
+
+        let shape = Shape { depth: 2, ..shape };
+        let mut board = Board::new(MineField::with_shape(shape, [(0, 0)]).unwrap());
+        board.reveal(5, 2);
+        assert!(matches!(board.get(1, 2), CellState::Visible(Cell::Clear)));
+        assert!(matches!(board.get(3, 0), CellState::Hidden));
+        assert!(matches!(board.outcome(), Outcome::Ongoing));
+    }
+
+    #[test]
+    fn toroidal_minefield() {
+        let shape = Shape {
+            nrows: 4,
+            ncols: 5,
+            topology: Topology::Toroidal,
+            ..Shape::default()
+        };
+        assert!(shape
+            .cells()
+            .all(|(ir, ic)| shape.neighbours(ir, ic).count() == 9));
+        assert!(shape.neighbours(0, 0).any(|nb| nb == (3, 4)));
+
+        let mut board = Board::new(MineField::with_shape(shape, [(0, 0)]).unwrap());
+        assert!(matches!(board.field().get(3, 4), Cell::Neighbouring(1)));
+        assert!(matches!(board.field().get(2, 2), Cell::Clear));
+        board.reveal(2, 2);
+        assert!(matches!(board.outcome(), Outcome::Won));
+    }
+
     #[test]
     fn rand_new_minefield_avoiding() {
-        let mf = MineField::with_rand_mines_avoiding(3, 3, 5, 0, 0);
+        let shape = Shape::square(3, 3);
+        let mf =
+            MineField::with_rand_mines_avoiding(shape, 5, 0, 0, &mut rand::thread_rng()).unwrap();
         assert!(matches!(mf.get(0, 0), Cell::Clear));
         assert!(matches!(mf.get(0, 1), Cell::Neighbouring(2)));
         assert!(matches!(mf.get(1, 1), Cell::Neighbouring(5)));
     }
 
+    #[test]
+    fn rand_new_minefield_no_guess() {
+        let mut rng = rand::thread_rng();
+        let shape = Shape::square(8, 8);
+        let (mf, solvable) =
+            MineField::with_rand_mines_no_guess(shape, 10, 0, 0, 100, &mut rng).unwrap();
+        assert!(solvable);
+        assert_eq!(mf.n_mines, 10);
+        assert!(solver::is_solvable_from(mf, 0, 0));
+
+        // Dense boards are repaired rather than drawn again.
+        let shape = Shape::square(16, 30);
+        let (mf, solvable) =
+            MineField::with_rand_mines_no_guess(shape, 99, 5, 5, 1000, &mut rng).unwrap();
+        assert!(solvable);
+        assert_eq!(mf.n_mines, 99);
+        assert!(solver::is_solvable_from(mf, 5, 5));
+    }
+
+    #[test]
+    fn saved_board_is_checked() {
+        let board = Board::new(MineField::new(2, 2, [(0, 0)]).unwrap());
+        let text = ron::to_string(&board).unwrap();
+        let loaded: Board = ron::from_str(&text).unwrap();
+        assert!(matches!(loaded.field().get(1, 1), Cell::Neighbouring(1)));
+        assert_eq!(loaded.nmines(), 1);
+
+        let load = |from: &str, to: &str| ron::from_str::<Board>(&text.replace(from, to));
+        // Clues are recomputed from the mines.
+        let loaded = load("Neighbouring(1),Neighbouring(1)]", "Clear,Clear]").unwrap();
+        assert!(matches!(loaded.field().get(1, 1), Cell::Neighbouring(1)));
+        assert!(load("state:[Hidden,", "state:[").is_err());
+        assert!(load("Mine(1),", "").is_err());
+        assert!(load("nrows:2", "nrows:0").is_err());
+        assert!(load("Mine(1)", "Mine(2)").is_err());
+    }
+
     #[test]
     fn board_reveal() {
-        let mut board = Board::new(MineField::new(5, 5, [(2, 2)]));
+        let mut board = Board::new(MineField::new(5, 5, [(2, 2)]).unwrap());
         board.reveal(1, 1);
         assert!(matches!(board.get(0, 0), CellState::Hidden));
         assert!(matches!(
@@ -227,4 +1236,17 @@ mod tests {
         board.reveal(4, 4);
         assert!(matches!(board.get(0, 0), CellState::Visible(Cell::Clear)));
     }
+
+    #[test]
+    fn board_serde_roundtrip() {
+        let mut board = Board::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        board.reveal(4, 4);
+        board.toggle_flag(2, 2);
+        let text = ron::to_string(&board).unwrap();
+        let board: Board = ron::from_str(&text).unwrap();
+        assert_eq!(board.nmines(), 1);
+        assert!(matches!(board.get(2, 2), CellState::Flagged(1)));
+        assert!(matches!(board.get(0, 0), CellState::Visible(Cell::Clear)));
+        assert!(matches!(board.field.get(2, 2), Cell::Mine(1)));
+    }
 }
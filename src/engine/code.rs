@@ -0,0 +1,286 @@
+//! Compact codes to share and replay a generated board.
+//!
+//! A code reads `<mode><rows>.<cols>.<mines>.<row>.<col>.<seed>`, where the
+//! mode is `s` for standard boards and `n` for no-guess boards, the numbers
+//! are written in base 36, and `<row>.<col>` is the first click. Board
+//! variants are appended as tags, `.hex` for hexagonal boards, `.torus` for
+//! wrap-around ones, `.l<depth>` for boards with several layers, `.k<mask>`
+//! for clues counting a non-standard [`Kernel`], `.m<n>` for cells holding
+//! up to `n` mines and `.a<n>` for boards with `n` anti-mines.
+
+use std::{fmt, str::FromStr};
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use super::{kernel::Kernel, Error, MineField, Shape, Topology};
+
+/// Cells scanned while repairing a no-guess board before giving up. Each
+/// repair goes over the whole board, so large boards get fewer of them.
+pub const NO_GUESS_BUDGET: usize = 500_000;
+
+/// Everything needed to generate the same board again.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameCode {
+    pub shape: Shape,
+    pub nmines: usize,
+    pub first_click: (usize, usize),
+    pub seed: u64,
+    pub no_guess: bool,
+}
+
+impl GameCode {
+    pub fn field(&self) -> Result<MineField, Error> {
+        self.generate().map(|(field, _)| field)
+    }
+
+    /// Generate the field, also telling whether it can be cleared without
+    /// guessing. Standard boards are not checked and reported as needing
+    /// guesses.
+    pub fn generate(&self) -> Result<(MineField, bool), Error> {
+        let (irow, icol) = self.first_click;
+        let mut rng = ChaCha8Rng::seed_from_u64(self.seed);
+        if self.no_guess {
+            MineField::with_rand_mines_no_guess(
+                self.shape,
+                self.nmines,
+                irow,
+                icol,
+                NO_GUESS_BUDGET / self.shape.ncells().max(1),
+                &mut rng,
+            )
+        } else {
+            MineField::with_rand_mines_avoiding(self.shape, self.nmines, irow, icol, &mut rng)
+                .map(|field| (field, false))
+        }
+    }
+}
+
+fn to_base36(mut n: u64) -> String {
+    let mut digits = Vec::new();
+    loop {
+        digits.push(char::from_digit((n % 36) as u32, 36).unwrap());
+        n /= 36;
+        if n == 0 {
+            break;
+        }
+    }
+    digits.iter().rev().collect()
+}
+
+impl fmt::Display for GameCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.no_guess { 'n' } else { 's' };
+        let fields = [
+            self.shape.nrows as u64,
+            self.shape.ncols as u64,
+            self.nmines as u64,
+            self.first_click.0 as u64,
+            self.first_click.1 as u64,
+            self.seed,
+        ];
+        let mut fields: Vec<_> = fields.into_iter().map(to_base36).collect();
+        match self.shape.topology {
+            Topology::Square => {}
+            Topology::Hexagonal => fields.push("hex".to_owned()),
+            Topology::Toroidal => fields.push("torus".to_owned()),
+        }
+        if self.shape.depth > 1 {
+            fields.push(format!("l{}", to_base36(self.shape.depth as u64)));
+        }
+        if self.shape.kernel != Kernel::STANDARD {
+            fields.push(format!("k{}", to_base36(self.shape.kernel.mask().into())));
+        }
+        if self.shape.mines_per_cell > 1 {
+            fields.push(format!("m{}", self.shape.mines_per_cell));
+        }
+        if self.shape.anti_mines > 0 {
+            fields.push(format!("a{}", to_base36(self.shape.anti_mines as u64)));
+        }
+        write!(f, "{mode}{}", fields.join("."))
+    }
+}
+
+#[derive(Debug)]
+pub struct InvalidGameCode;
+
+impl fmt::Display for InvalidGameCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("invalid game code")
+    }
+}
+
+impl std::error::Error for InvalidGameCode {}
+
+impl FromStr for GameCode {
+    type Err = InvalidGameCode;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let no_guess = match s.chars().next() {
+            Some('n') => true,
+            Some('s') => false,
+            _ => return Err(InvalidGameCode),
+        };
+        let mut fields = s[1..].split('.');
+        let numbers = fields
+            .by_ref()
+            .take(6)
+            .map(|f| u64::from_str_radix(f, 36).map_err(|_| InvalidGameCode))
+            .collect::<Result<Vec<_>, _>>()?;
+        let &[nrows, ncols, nmines, irow, icol, seed] = numbers.as_slice() else {
+            return Err(InvalidGameCode);
+        };
+        let mut topology = Topology::Square;
+        let mut depth = 1;
+        let mut kernel = Kernel::STANDARD;
+        let mut mines_per_cell = 1;
+        let mut anti_mines = 0;
+        for tag in fields {
+            match tag {
+                "hex" => topology = Topology::Hexagonal,
+                "torus" => topology = Topology::Toroidal,
+                _ if tag.starts_with('l') => {
+                    depth = u64::from_str_radix(&tag[1..], 36).map_err(|_| InvalidGameCode)?;
+                }
+                _ if tag.starts_with('k') => {
+                    kernel = u32::from_str_radix(&tag[1..], 36)
+                        .ok()
+                        .and_then(Kernel::from_mask)
+                        .ok_or(InvalidGameCode)?;
+                }
+                _ if tag.starts_with('m') => {
+                    mines_per_cell = tag[1..].parse().map_err(|_| InvalidGameCode)?;
+                }
+                _ if tag.starts_with('a') => {
+                    anti_mines = u64::from_str_radix(&tag[1..], 36).map_err(|_| InvalidGameCode)?;
+                }
+                _ => return Err(InvalidGameCode),
+            }
+        }
+        let to_usize = |n: u64| usize::try_from(n).map_err(|_| InvalidGameCode);
+        let code = GameCode {
+            shape: Shape {
+                nrows: to_usize(nrows)?,
+                ncols: to_usize(ncols)?,
+                topology,
+                depth: to_usize(depth)?,
+                kernel,
+                mines_per_cell,
+                anti_mines: to_usize(anti_mines)?,
+            },
+            nmines: to_usize(nmines)?,
+            first_click: (to_usize(irow)?, to_usize(icol)?),
+            seed,
+            no_guess,
+        };
+        if code.shape.check().is_err()
+            || !code.shape.contains(code.first_click.0, code.first_click.1)
+            || code
+                .nmines
+                .checked_add(code.shape.anti_mines)
+                .is_none_or(|n| n > code.shape.ncells() * usize::from(mines_per_cell))
+        {
+            return Err(InvalidGameCode);
+        }
+        Ok(code)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Cell;
+
+    #[test]
+    fn code_roundtrip() {
+        let code = GameCode {
+            shape: Shape::square(16, 30),
+            nmines: 99,
+            first_click: (3, 27),
+            seed: u64::MAX,
+            no_guess: true,
+        };
+        let text = code.to_string();
+        assert_eq!(text, "ng.u.2r.3.r.3w5e11264sgsf");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+
+        let code = GameCode {
+            shape: Shape {
+                topology: Topology::Hexagonal,
+                ..code.shape
+            },
+            ..code
+        };
+        let text = code.to_string();
+        assert_eq!(text, "ng.u.2r.3.r.3w5e11264sgsf.hex");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+
+        let code = GameCode {
+            shape: Shape {
+                depth: 3,
+                ..code.shape
+            },
+            first_click: (40, 27),
+            ..code
+        };
+        let text = code.to_string();
+        assert_eq!(text, "ng.u.2r.14.r.3w5e11264sgsf.hex.l3");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+
+        let code = GameCode {
+            shape: Shape {
+                topology: Topology::Square,
+                kernel: Kernel::KNIGHT,
+                ..code.shape
+            },
+            ..code
+        };
+        let text = code.to_string();
+        assert!(text.ends_with(".l3.k6ksa2"), "{text}");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+
+        let code = GameCode {
+            shape: Shape {
+                mines_per_cell: 3,
+                ..code.shape
+            },
+            ..code
+        };
+        let text = code.to_string();
+        assert!(text.ends_with(".k6ksa2.m3"), "{text}");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+
+        let code = GameCode {
+            shape: Shape {
+                anti_mines: 40,
+                ..code.shape
+            },
+            ..code
+        };
+        let text = code.to_string();
+        assert!(text.ends_with(".m3.a14"), "{text}");
+        assert_eq!(text.parse::<GameCode>().unwrap(), code);
+        assert!("sg.u.2r.3.r.1.m4".parse::<GameCode>().is_err());
+        assert!("ng.u.2r.3.r.1.cube".parse::<GameCode>().is_err());
+        assert!("xg.u.2r.3.r.1".parse::<GameCode>().is_err());
+        assert!("sg.u.2r.g.r.1".parse::<GameCode>().is_err());
+        assert!("szzzzzzzzzzzz.zzzzzzzzzzzz.1.0.0.1"
+            .parse::<GameCode>()
+            .is_err());
+        assert!("s1000.1000.1.0.0.1".parse::<GameCode>().is_err());
+    }
+
+    #[test]
+    fn same_seed_same_field() {
+        let code: GameCode = "sg.g.14.0.0.2a".parse().unwrap();
+        let (f1, f2) = (code.field().unwrap(), code.field().unwrap());
+        for (ir, ic) in code.shape.cells() {
+            assert_eq!(
+                matches!(f1.get(ir, ic), Cell::Mine(_)),
+                matches!(f2.get(ir, ic), Cell::Mine(_))
+            );
+        }
+    }
+}
@@ -0,0 +1,270 @@
+//! Endless boards without edges, generated chunk by chunk as they are
+//! explored.
+//!
+//! The plane is split into square chunks of [`CHUNK_SIZE`] cells. The mines
+//! of a chunk are drawn from the seed and the coordinates of the chunk only,
+//! so that chunks can be generated in any order. Clues of a chunk need the
+//! mines of the chunks around it, and are computed the first time one of its
+//! cells is revealed. Cells around the origin never hold mines, the game
+//! starting there.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use rand::{seq::IteratorRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::{Cell, CellState, Error};
+
+/// Number of rows and columns of a chunk.
+pub const CHUNK_SIZE: i64 = 16;
+
+const CHUNK_CELLS: usize = (CHUNK_SIZE * CHUNK_SIZE) as usize;
+
+/// Largest number of cells revealed at once. Sparse fields can have huge
+/// openings, the rest of which is revealed by clicking their hidden edge.
+const MAX_FLOOD: usize = 100_000;
+
+/// Chunk holding a cell, and index of the cell in the chunk.
+fn locate(irow: i64, icol: i64) -> ((i64, i64), usize) {
+    let chunk = (irow.div_euclid(CHUNK_SIZE), icol.div_euclid(CHUNK_SIZE));
+    let idx = irow.rem_euclid(CHUNK_SIZE) * CHUNK_SIZE + icol.rem_euclid(CHUNK_SIZE);
+    (chunk, idx as usize)
+}
+
+fn neighbours(irow: i64, icol: i64) -> impl Iterator<Item = (i64, i64)> {
+    (irow - 1..=irow + 1).flat_map(move |ir| (icol - 1..=icol + 1).map(move |ic| (ir, ic)))
+}
+
+/// Minefield over the whole plane, generated lazily.
+#[derive(Clone)]
+pub struct EndlessField {
+    seed: u64,
+    mines_per_chunk: usize,
+    /// Mines of the chunks drawn so far.
+    mines: BTreeMap<(i64, i64), Vec<bool>>,
+    /// Cells of the chunks whose clues are computed.
+    chunks: BTreeMap<(i64, i64), Vec<Cell>>,
+}
+
+impl EndlessField {
+    pub fn new(seed: u64, mines_per_chunk: usize) -> Result<Self, Error> {
+        if mines_per_chunk > CHUNK_CELLS - 9 {
+            return Err(Error::TooManyMines {
+                requested: mines_per_chunk,
+                available: CHUNK_CELLS - 9,
+            });
+        }
+        Ok(Self {
+            seed,
+            mines_per_chunk,
+            mines: BTreeMap::new(),
+            chunks: BTreeMap::new(),
+        })
+    }
+
+    fn chunk_mines(&mut self, chunk: (i64, i64)) -> &[bool] {
+        let (seed, nmines) = (self.seed, self.mines_per_chunk);
+        self.mines.entry(chunk).or_insert_with(|| {
+            // Each chunk has its own stream of the seeded generator.
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            rng.set_stream((u64::from(chunk.0 as u32) << 32) | u64::from(chunk.1 as u32));
+            let (row0, col0) = (chunk.0 * CHUNK_SIZE, chunk.1 * CHUNK_SIZE);
+            let mut mines = vec![false; CHUNK_CELLS];
+            let free = (0..CHUNK_CELLS).filter(|&idx| {
+                let irow = row0 + idx as i64 / CHUNK_SIZE;
+                let icol = col0 + idx as i64 % CHUNK_SIZE;
+                irow.abs() > 1 || icol.abs() > 1
+            });
+            for idx in free.choose_multiple(&mut rng, nmines) {
+                mines[idx] = true;
+            }
+            mines
+        })
+    }
+
+    fn is_mine(&mut self, irow: i64, icol: i64) -> bool {
+        let (chunk, idx) = locate(irow, icol);
+        self.chunk_mines(chunk)[idx]
+    }
+
+    pub fn get(&mut self, irow: i64, icol: i64) -> Cell {
+        let (chunk, idx) = locate(irow, icol);
+        if !self.chunks.contains_key(&chunk) {
+            let (row0, col0) = (chunk.0 * CHUNK_SIZE, chunk.1 * CHUNK_SIZE);
+            let cells = (0..CHUNK_CELLS as i64)
+                .map(|idx| {
+                    let (ir, ic) = (row0 + idx / CHUNK_SIZE, col0 + idx % CHUNK_SIZE);
+                    if self.is_mine(ir, ic) {
+                        return Cell::Mine(1);
+                    }
+                    match neighbours(ir, ic)
+                        .filter(|&(jr, jc)| self.is_mine(jr, jc))
+                        .count()
+                    {
+                        0 => Cell::Clear,
+                        n => Cell::Neighbouring(n as i16),
+                    }
+                })
+                .collect();
+            self.chunks.insert(chunk, cells);
+        }
+        self.chunks[&chunk][idx]
+    }
+}
+
+/// Player's view of an [`EndlessField`], with only the cells that are not
+/// hidden being stored.
+#[derive(Clone)]
+pub struct EndlessBoard {
+    field: EndlessField,
+    state: BTreeMap<(i64, i64), CellState>,
+    cleared: usize,
+    lost: bool,
+}
+
+impl EndlessBoard {
+    /// Board with the origin already revealed.
+    pub fn new(field: EndlessField) -> Self {
+        let mut board = Self {
+            field,
+            state: BTreeMap::new(),
+            cleared: 0,
+            lost: false,
+        };
+        board.reveal(0, 0);
+        board
+    }
+
+    pub fn get(&self, irow: i64, icol: i64) -> CellState {
+        self.state
+            .get(&(irow, icol))
+            .copied()
+            .unwrap_or(CellState::Hidden)
+    }
+
+    /// Number of safe cells revealed, the score of the game.
+    pub fn score(&self) -> usize {
+        self.cleared
+    }
+
+    pub fn is_lost(&self) -> bool {
+        self.lost
+    }
+
+    /// Reveal a cell and the openings it leads to, returning the newly
+    /// revealed cells. Nothing happens once the game is lost.
+    pub fn reveal(&mut self, irow: i64, icol: i64) -> Vec<(i64, i64)> {
+        let mut revealed = Vec::new();
+        let mut queue = VecDeque::from([(irow, icol)]);
+        while let Some((ir, ic)) = queue.pop_front() {
+            if self.lost || revealed.len() >= MAX_FLOOD {
+                break;
+            }
+            if let CellState::Visible(_) = self.get(ir, ic) {
+                continue;
+            }
+            let cell = self.field.get(ir, ic);
+            self.state.insert((ir, ic), CellState::Visible(cell));
+            revealed.push((ir, ic));
+            match cell {
+                Cell::Mine(_) | Cell::AntiMine => self.lost = true,
+                Cell::Clear => {
+                    self.cleared += 1;
+                    queue
+                        .extend(neighbours(ir, ic).filter(|&(jr, jc)| {
+                            !matches!(self.get(jr, jc), CellState::Visible(_))
+                        }));
+                }
+                Cell::Neighbouring(_) => self.cleared += 1,
+            }
+        }
+        revealed
+    }
+
+    /// Reveal the hidden neighbours of a clue with as many flags around it
+    /// as its number.
+    pub fn reveal_around_nb(&mut self, irow: i64, icol: i64) {
+        if let CellState::Visible(Cell::Neighbouring(n_nb)) = self.get(irow, icol) {
+            let n_flagged: usize = neighbours(irow, icol)
+                .map(|(ir, ic)| self.get(ir, ic).nflags())
+                .sum();
+            if n_flagged as i16 == n_nb {
+                for (ir, ic) in neighbours(irow, icol) {
+                    if self.get(ir, ic).is_hidden() {
+                        self.reveal(ir, ic);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Flag the hidden neighbours of a clue missing as many mines as there
+    /// are hidden cells around it.
+    pub fn flag_around_nb(&mut self, irow: i64, icol: i64) {
+        if self.lost {
+            return;
+        }
+        if let CellState::Visible(Cell::Neighbouring(n_nb)) = self.get(irow, icol) {
+            let (flagged, hidden): (Vec<_>, Vec<_>) = neighbours(irow, icol)
+                .filter(|&(ir, ic)| !matches!(self.get(ir, ic), CellState::Visible(_)))
+                .partition(|&(ir, ic)| self.get(ir, ic).nflags() > 0);
+            if !hidden.is_empty() && (flagged.len() + hidden.len()) as i16 == n_nb {
+                for cell in hidden {
+                    self.state.insert(cell, CellState::Flagged(1));
+                }
+            }
+        }
+    }
+
+    pub fn toggle_flag(&mut self, irow: i64, icol: i64) {
+        if self.lost {
+            return;
+        }
+        match self.get(irow, icol) {
+            CellState::Hidden => {
+                self.state.insert((irow, icol), CellState::Flagged(1));
+            }
+            CellState::Flagged(_) => {
+                self.state.remove(&(irow, icol));
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chunks_are_reproducible() {
+        let mut f1 = EndlessField::new(7, 50).unwrap();
+        let mut f2 = EndlessField::new(7, 50).unwrap();
+        // Chunks generated in different orders hold the same cells.
+        let cells: Vec<_> = (-40..40).map(|i| f1.get(i, 3 * i)).collect();
+        let mut back: Vec<_> = (-40..40).rev().map(|i| f2.get(i, 3 * i)).collect();
+        back.reverse();
+        assert_eq!(cells, back);
+        assert_eq!(f1.mines[&(0, 0)].iter().filter(|&&m| m).count(), 50);
+        assert!(neighbours(0, 0).all(|(ir, ic)| !f1.is_mine(ir, ic)));
+    }
+
+    #[test]
+    fn endless_game() {
+        let mut board = EndlessBoard::new(EndlessField::new(3, 40).unwrap());
+        assert!(matches!(board.get(0, 0), CellState::Visible(Cell::Clear)));
+        assert!(board.score() >= 9);
+        let mine = (2..)
+            .map(|icol| (0, icol))
+            .find(|&(ir, ic)| board.field.is_mine(ir, ic))
+            .unwrap();
+        board.toggle_flag(mine.0, mine.1);
+        assert_eq!(board.get(mine.0, mine.1).nflags(), 1);
+        board.toggle_flag(mine.0, mine.1);
+        let score = board.score();
+        assert_eq!(board.reveal(mine.0, mine.1).len(), 1);
+        assert!(board.is_lost());
+        assert_eq!(board.score(), score);
+        assert!(board.reveal(-100, -100).is_empty());
+    }
+}
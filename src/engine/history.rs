@@ -0,0 +1,122 @@
+//! Undo and redo of the actions played on a [`Board`].
+
+use serde::{Deserialize, Serialize};
+
+use super::{replay::Action, Board, CellState};
+
+/// Cells modified by an action, with their state before and after it.
+#[derive(Clone, Serialize, Deserialize)]
+struct Change(Vec<(usize, CellState, CellState)>);
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct History {
+    done: Vec<Change>,
+    undone: Vec<Change>,
+}
+
+impl History {
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Whether the changes only touch cells of the board, which a history
+    /// loaded from a file may not.
+    pub fn fits(&self, board: &Board) -> bool {
+        let ncells = board.state.len();
+        self.done
+            .iter()
+            .chain(&self.undone)
+            .all(|change| change.0.iter().all(|&(icell, ..)| icell < ncells))
+    }
+
+    /// Play an action on the board, keeping track of what it changed.
+    ///
+    /// Returns whether the board was modified. Actions that modify the board
+    /// clear the redo stack.
+    pub fn play(&mut self, board: &mut Board, action: Action) -> bool {
+        match action {
+            Action::Undo => self.undo(board),
+            Action::Redo => self.redo(board),
+            _ => {
+                let before = board.state.clone();
+                action.apply(board);
+                let change: Vec<_> = before
+                    .into_iter()
+                    .zip(&board.state)
+                    .enumerate()
+                    .filter(|(_, (old, new))| old != *new)
+                    .map(|(icell, (old, new))| (icell, old, *new))
+                    .collect();
+                if change.is_empty() {
+                    return false;
+                }
+                self.done.push(Change(change));
+                self.undone.clear();
+                true
+            }
+        }
+    }
+
+    fn undo(&mut self, board: &mut Board) -> bool {
+        let Some(change) = self.done.pop() else {
+            return false;
+        };
+        for &(icell, old, _) in &change.0 {
+            board.set(icell, old);
+        }
+        self.undone.push(change);
+        true
+    }
+
+    fn redo(&mut self, board: &mut Board) -> bool {
+        let Some(change) = self.undone.pop() else {
+            return false;
+        };
+        for &(icell, _, new) in &change.0 {
+            board.set(icell, new);
+        }
+        self.done.push(change);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Cell, MineField, Outcome};
+
+    #[test]
+    fn undo_redo() {
+        let mut board = Board::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        let mut history = History::default();
+        assert!(history.play(&mut board, Action::Reveal(1, 1)));
+        assert!(!history.play(&mut board, Action::Reveal(1, 1)));
+        assert!(history.play(&mut board, Action::Reveal(2, 2)));
+        assert!(matches!(board.outcome(), Outcome::Lost));
+
+        assert!(history.play(&mut board, Action::Undo));
+        assert!(matches!(board.outcome(), Outcome::Ongoing));
+        assert!(matches!(board.get(2, 2), CellState::Hidden));
+        assert!(matches!(
+            board.get(1, 1),
+            CellState::Visible(Cell::Neighbouring(1))
+        ));
+
+        assert!(history.play(&mut board, Action::Undo));
+        assert!(matches!(board.get(1, 1), CellState::Hidden));
+        assert!(!history.can_undo());
+        assert!(!history.play(&mut board, Action::Undo));
+
+        assert!(history.play(&mut board, Action::Redo));
+        assert!(matches!(
+            board.get(1, 1),
+            CellState::Visible(Cell::Neighbouring(1))
+        ));
+        assert!(history.play(&mut board, Action::ToggleFlag(2, 2)));
+        assert!(!history.can_redo());
+    }
+}
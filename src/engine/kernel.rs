@@ -0,0 +1,177 @@
+//! Neighbourhoods counted by the clues of square cells.
+
+use serde::{Deserialize, Serialize};
+
+/// How far from its cell a clue can count mines, in rows and columns.
+pub const RADIUS: isize = 2;
+
+const WIDTH: isize = 2 * RADIUS + 1;
+
+/// Offsets of the cells counted by a clue, as a mask over the square of
+/// side `2 * RADIUS + 1` centered on the clue.
+///
+/// The center is always part of the kernel: a cell counts as its own
+/// neighbour, which flood fill and the first click rely on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Kernel(u32);
+
+impl Kernel {
+    /// The 8 surrounding cells.
+    pub const STANDARD: Self = Self::from_offsets(&[
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+        (0, -1),
+        (0, 1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+    ]);
+    /// The 4 orthogonally adjacent cells.
+    pub const ORTHOGONAL: Self = Self::from_offsets(&[(-1, 0), (0, -1), (0, 1), (1, 0)]);
+    /// The cells up to 2 steps away along rows and columns.
+    pub const CROSS: Self = Self::from_offsets(&[
+        (-2, 0),
+        (-1, 0),
+        (0, -2),
+        (0, -1),
+        (0, 1),
+        (0, 2),
+        (1, 0),
+        (2, 0),
+    ]);
+    /// The 8 cells a chess knight can reach.
+    pub const KNIGHT: Self = Self::from_offsets(&[
+        (-2, -1),
+        (-2, 1),
+        (-1, -2),
+        (-1, 2),
+        (1, -2),
+        (1, 2),
+        (2, -1),
+        (2, 1),
+    ]);
+    /// The 16 cells at distance 2, skipping the surrounding ones.
+    pub const RING: Self = Self::from_offsets(&[
+        (-2, -2),
+        (-2, -1),
+        (-2, 0),
+        (-2, 1),
+        (-2, 2),
+        (-1, -2),
+        (-1, 2),
+        (0, -2),
+        (0, 2),
+        (1, -2),
+        (1, 2),
+        (2, -2),
+        (2, -1),
+        (2, 0),
+        (2, 1),
+        (2, 2),
+    ]);
+
+    /// Named kernels offered to players.
+    pub const PRESETS: [(&'static str, Self); 5] = [
+        ("Standard", Self::STANDARD),
+        ("Orthogonal", Self::ORTHOGONAL),
+        ("Cross", Self::CROSS),
+        ("Knight", Self::KNIGHT),
+        ("Ring", Self::RING),
+    ];
+
+    const fn bit(drow: isize, dcol: isize) -> u32 {
+        1 << ((drow + RADIUS) * WIDTH + dcol + RADIUS)
+    }
+
+    /// Kernel made of the given offsets, which must be at most `RADIUS` away.
+    pub const fn from_offsets(offsets: &[(isize, isize)]) -> Self {
+        let mut mask = Self::bit(0, 0);
+        let mut i = 0;
+        while i < offsets.len() {
+            let (drow, dcol) = offsets[i];
+            assert!(drow.abs() <= RADIUS && dcol.abs() <= RADIUS);
+            mask |= Self::bit(drow, dcol);
+            i += 1;
+        }
+        Self(mask)
+    }
+
+    /// Kernel from its mask, as given by [`Kernel::mask`].
+    pub fn from_mask(mask: u32) -> Option<Self> {
+        let all = (1 << (WIDTH * WIDTH)) - 1;
+        (mask & !all == 0).then_some(Self(mask | Self::bit(0, 0)))
+    }
+
+    pub fn mask(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, drow: isize, dcol: isize) -> bool {
+        drow.abs() <= RADIUS && dcol.abs() <= RADIUS && self.0 & Self::bit(drow, dcol) != 0
+    }
+
+    /// Add or remove an offset. The center can't be removed.
+    pub fn toggle(&mut self, drow: isize, dcol: isize) {
+        if (drow, dcol) != (0, 0) {
+            self.0 ^= Self::bit(drow, dcol);
+        }
+    }
+
+    /// Name of the kernel if it is one of the presets.
+    pub fn name(&self) -> Option<&'static str> {
+        Self::PRESETS
+            .iter()
+            .find(|(_, kernel)| kernel == self)
+            .map(|(name, _)| *name)
+    }
+
+    /// Largest distance of the offsets along rows or columns.
+    pub fn reach(&self) -> usize {
+        self.offsets()
+            .map(|(drow, dcol)| drow.unsigned_abs().max(dcol.unsigned_abs()))
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Offsets of the kernel, the center included.
+    pub fn offsets(self) -> impl Iterator<Item = (isize, isize)> {
+        (-RADIUS..=RADIUS)
+            .flat_map(|drow| (-RADIUS..=RADIUS).map(move |dcol| (drow, dcol)))
+            .filter(move |&(drow, dcol)| self.contains(drow, dcol))
+    }
+}
+
+impl Default for Kernel {
+    fn default() -> Self {
+        Self::STANDARD
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn kernel_offsets() {
+        assert_eq!(Kernel::STANDARD.offsets().count(), 9);
+        assert_eq!(Kernel::KNIGHT.offsets().count(), 9);
+        assert_eq!(Kernel::RING.offsets().count(), 17);
+        assert_eq!(Kernel::ORTHOGONAL.reach(), 1);
+        assert_eq!(Kernel::CROSS.reach(), 2);
+
+        let mut kernel = Kernel::ORTHOGONAL;
+        kernel.toggle(0, 0);
+        assert!(kernel.contains(0, 0));
+        kernel.toggle(-1, 0);
+        kernel.toggle(2, 2);
+        assert_eq!(
+            kernel.offsets().collect::<Vec<_>>(),
+            [(0, -1), (0, 0), (0, 1), (1, 0), (2, 2)]
+        );
+        assert_eq!(kernel.name(), None);
+        assert_eq!(Kernel::from_mask(kernel.mask()), Some(kernel));
+        assert_eq!(Kernel::from_mask(1 << 25), None);
+        assert_eq!(Kernel::from_mask(0), Some(Kernel::from_offsets(&[])));
+    }
+}
@@ -0,0 +1,155 @@
+//! Complexity metrics of a generated [`MineField`].
+
+use super::{Cell, MineField};
+
+impl MineField {
+    /// Number of openings, the fewest clicks on [`Cell::Clear`] that reveal
+    /// all of them. With one-directional kernels, an opening can reveal
+    /// another one without being revealed by it.
+    pub fn openings(&self) -> usize {
+        let shape = self.shape;
+        let is_clear = |(ir, ic): (usize, usize)| matches!(self.get(ir, ic), Cell::Clear);
+        // Clear cells in the order their search finishes: a cell that reaches
+        // another one finishes after it unless it is reached back.
+        let mut seen = vec![false; shape.ncells()];
+        let mut finished = Vec::new();
+        for (irow, icol) in shape.cells().filter(|&cell| is_clear(cell)) {
+            if seen[shape.idx(irow, icol)] {
+                continue;
+            }
+            seen[shape.idx(irow, icol)] = true;
+            let mut stack = vec![((irow, icol), shape.neighbours(irow, icol))];
+            while let Some((cell, neighbours)) = stack.last_mut() {
+                match neighbours.find(|&(jr, jc)| is_clear((jr, jc)) && !seen[shape.idx(jr, jc)]) {
+                    Some((jr, jc)) => {
+                        seen[shape.idx(jr, jc)] = true;
+                        stack.push(((jr, jc), shape.neighbours(jr, jc)));
+                    }
+                    None => {
+                        finished.push(*cell);
+                        stack.pop();
+                    }
+                }
+            }
+        }
+        // Cells finishing last are revealed by no other opening.
+        let mut seen = vec![false; shape.ncells()];
+        let mut n_openings = 0;
+        for &(irow, icol) in finished.iter().rev() {
+            if seen[shape.idx(irow, icol)] {
+                continue;
+            }
+            n_openings += 1;
+            seen[shape.idx(irow, icol)] = true;
+            let mut stack = vec![(irow, icol)];
+            while let Some((ir, ic)) = stack.pop() {
+                for (jr, jc) in shape.neighbours(ir, ic) {
+                    let jcell = shape.idx(jr, jc);
+                    if !seen[jcell] && matches!(self.cells[jcell], Cell::Clear) {
+                        seen[jcell] = true;
+                        stack.push((jr, jc));
+                    }
+                }
+            }
+        }
+        n_openings
+    }
+
+    /// Number of numbered cells that no opening reveals.
+    ///
+    /// Openings reveal the neighbours of their cells, which with asymmetric
+    /// kernels are not the cells that see them.
+    pub fn isolated_numbers(&self) -> usize {
+        let shape = self.shape;
+        let mut revealed = vec![false; shape.ncells()];
+        for (irow, icol) in shape.cells() {
+            if matches!(self.get(irow, icol), Cell::Clear) {
+                for (jr, jc) in shape.neighbours(irow, icol) {
+                    revealed[shape.idx(jr, jc)] = true;
+                }
+            }
+        }
+        shape
+            .cells()
+            .filter(|&(ir, ic)| matches!(self.get(ir, ic), Cell::Neighbouring(_)))
+            .filter(|&(ir, ic)| !revealed[shape.idx(ir, ic)])
+            .count()
+    }
+
+    /// Bechtel's Board Benchmark Value: the minimum number of clicks needed
+    /// to clear the board without flagging.
+    pub fn three_bv(&self) -> usize {
+        self.openings() + self.isolated_numbers()
+    }
+
+    /// `clue_counts()[n]` is the number of cells with `n` neighbouring mines,
+    /// `clue_counts()[0]` being the number of [`Cell::Clear`]. Counts go up to
+    /// the largest number on the board, and at least to 8. Negative clues,
+    /// next to anti-mines, are left out.
+    pub fn clue_counts(&self) -> Vec<usize> {
+        let mut counts = vec![0; 9];
+        for cell in &self.cells {
+            let n = match *cell {
+                Cell::Clear => 0,
+                Cell::Neighbouring(n) => match usize::try_from(n) {
+                    Ok(n) => n,
+                    Err(_) => continue,
+                },
+                Cell::Mine(_) | Cell::AntiMine => continue,
+            };
+            if n >= counts.len() {
+                counts.resize(n + 1, 0);
+            }
+            counts[n] += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::{kernel::Kernel, MineField, Shape};
+
+    #[test]
+    fn board_metrics() {
+        // . 1 * 1 .
+        // . 1 1 1 .
+        // 1 1 . . .
+        // * 1 . . .
+        let mf = MineField::new(4, 5, [(0, 2), (3, 0)]).unwrap();
+        assert_eq!(mf.openings(), 2);
+        assert_eq!(mf.isolated_numbers(), 0);
+        assert_eq!(mf.three_bv(), 2);
+        assert_eq!(mf.clue_counts(), [10, 8, 0, 0, 0, 0, 0, 0, 0]);
+
+        let mf = MineField::new(3, 3, [(1, 1)]).unwrap();
+        assert_eq!(mf.openings(), 0);
+        assert_eq!(mf.isolated_numbers(), 8);
+        assert_eq!(mf.three_bv(), 8);
+
+        // Each cell sees the one on its right: the 1 doesn't see the
+        // opening on its left, but the opening reveals it.
+        // . . 1 *
+        let shape = Shape {
+            nrows: 1,
+            ncols: 4,
+            kernel: Kernel::from_offsets(&[(0, 1)]),
+            ..Shape::default()
+        };
+        let mf = MineField::with_shape(shape, [(0, 3)]).unwrap();
+        assert_eq!(mf.openings(), 1);
+        assert_eq!(mf.isolated_numbers(), 0);
+
+        // Each cell sees the one on its left: clicking the last cell clears
+        // the board, the other opening being revealed on the way.
+        // * 1 . .
+        let shape = Shape {
+            kernel: Kernel::from_offsets(&[(0, -1)]),
+            ..shape
+        };
+        let mf = MineField::with_shape(shape, [(0, 0)]).unwrap();
+        assert_eq!(mf.openings(), 1);
+        assert_eq!(mf.isolated_numbers(), 0);
+        assert_eq!(mf.three_bv(), 1);
+    }
+}
@@ -0,0 +1,441 @@
+//! Exact mine probabilities from the player's view of a [`Board`].
+//!
+//! Hidden cells next to a clue form the frontier. The frontier is split into
+//! groups of cells that share no clue, the solutions of each group are
+//! enumerated, and the groups are combined with the cells away from the
+//! frontier, weighting each configuration by the number of ways to place the
+//! remaining mines. Counts are kept as big integers since the number of
+//! layouts explodes on large boards. As for the solver, flags are trusted.
+//!
+//! When cells can hold several mines, each of the `c` mines a cell can hold
+//! is a slot, mines being spread uniformly over the slots: a cell holding
+//! `k` mines counts for `C(c, k)` layouts.
+//!
+//! The enumeration grows exponentially with the size of the groups, so it
+//! gives up past [`MAX_SEARCH_NODES`] steps.
+
+use std::collections::BTreeMap;
+
+use num_bigint::BigUint;
+use num_traits::{One, ToPrimitive, Zero};
+
+use super::{
+    solver::{self, Constraint},
+    Board, CellState, Shape,
+};
+
+/// Largest number of partial layouts tried over all the groups.
+pub const MAX_SEARCH_NODES: usize = 200_000;
+
+/// Probability of each hidden cell holding a mine.
+pub struct MineProbabilities {
+    shape: Shape,
+    probs: Vec<Option<f64>>,
+}
+
+impl MineProbabilities {
+    /// Probability that the cell is a mine, `None` if it is not hidden.
+    pub fn get(&self, irow: usize, icol: usize) -> Option<f64> {
+        self.probs[self.shape.idx(irow, icol)]
+    }
+
+    /// The hidden cell with the lowest probability of being a mine.
+    pub fn safest(&self) -> Option<((usize, usize), f64)> {
+        self.shape
+            .cells()
+            .filter_map(|(ir, ic)| self.get(ir, ic).map(|p| ((ir, ic), p)))
+            .min_by(|(_, p1), (_, p2)| p1.total_cmp(p2))
+    }
+}
+
+/// Independent part of the frontier.
+struct Group {
+    cells: Vec<(usize, usize)>,
+    constraints: Vec<Constraint>,
+}
+
+/// Solutions of a group, by number of mines in the group.
+struct GroupSolutions {
+    /// Number of layouts with `k` mines.
+    count: Vec<BigUint>,
+    /// For each cell, number of layouts with `k` mines where the cell holds
+    /// a mine.
+    cell_count: Vec<Vec<BigUint>>,
+}
+
+fn split_groups(constraints: Vec<Constraint>) -> Vec<Group> {
+    let mut parent: Vec<usize> = (0..constraints.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    let mut owner: BTreeMap<(usize, usize), usize> = BTreeMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        for &cell in &constraint.cells {
+            if let Some(&j) = owner.get(&cell) {
+                let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                parent[ri] = rj;
+            } else {
+                owner.insert(cell, i);
+            }
+        }
+    }
+    let mut groups: BTreeMap<usize, Group> = BTreeMap::new();
+    for (i, constraint) in constraints.into_iter().enumerate() {
+        let group = groups.entry(root(&mut parent, i)).or_insert(Group {
+            cells: Vec::new(),
+            constraints: Vec::new(),
+        });
+        group.constraints.push(constraint);
+    }
+    for (cell, i) in owner {
+        let ri = root(&mut parent, i);
+        groups.get_mut(&ri).unwrap().cells.push(cell);
+    }
+    groups.into_values().collect()
+}
+
+impl Group {
+    /// Enumerate the solutions, or return `None` if that takes more than the
+    /// remaining `budget` of search nodes.
+    fn solve(&self, capacity: usize, budget: &mut usize) -> Option<GroupSolutions> {
+        let ncells = self.cells.len();
+        let index: BTreeMap<_, _> = self
+            .cells
+            .iter()
+            .enumerate()
+            .map(|(i, &c)| (c, i))
+            .collect();
+        let cell_constraints: Vec<Vec<usize>> = {
+            let mut cc = vec![Vec::new(); ncells];
+            for (j, constraint) in self.constraints.iter().enumerate() {
+                for cell in &constraint.cells {
+                    cc[index[cell]].push(j);
+                }
+            }
+            cc
+        };
+        let max_mines = capacity * ncells;
+        let mut search = Search {
+            cell_constraints: &cell_constraints,
+            capacity,
+            cell_weights: binomials(capacity),
+            missing: self.constraints.iter().map(|c| c.mines).collect(),
+            unassigned: self.constraints.iter().map(|c| c.cells.len()).collect(),
+            assignment: vec![0; ncells],
+            solutions: GroupSolutions {
+                count: vec![BigUint::zero(); max_mines + 1],
+                cell_count: vec![vec![BigUint::zero(); max_mines + 1]; ncells],
+            },
+            budget: *budget,
+        };
+        let done = search.run(0, 0);
+        *budget = search.budget;
+        done.then_some(search.solutions)
+    }
+}
+
+/// Backtracking over the number of mines in each cell of a group.
+struct Search<'a> {
+    cell_constraints: &'a [Vec<usize>],
+    capacity: usize,
+    /// Number of layouts of a cell holding `k` mines.
+    cell_weights: Vec<BigUint>,
+    missing: Vec<usize>,
+    unassigned: Vec<usize>,
+    assignment: Vec<usize>,
+    solutions: GroupSolutions,
+    /// Number of nodes left to visit.
+    budget: usize,
+}
+
+impl Search<'_> {
+    /// Visit the layouts of the remaining cells, returning `false` if the
+    /// budget ran out.
+    fn run(&mut self, icell: usize, nmines: usize) -> bool {
+        let Some(budget) = self.budget.checked_sub(1) else {
+            return false;
+        };
+        self.budget = budget;
+        if icell == self.assignment.len() {
+            let weight = self
+                .assignment
+                .iter()
+                .filter(|&&k| k > 0 && k < self.capacity)
+                .fold(BigUint::one(), |w, &k| w * &self.cell_weights[k]);
+            self.solutions.count[nmines] += &weight;
+            for (i, &k) in self.assignment.iter().enumerate() {
+                if k > 0 {
+                    self.solutions.cell_count[i][nmines] += &weight;
+                }
+            }
+            return true;
+        }
+        for k in 0..=self.capacity {
+            let constraints = &self.cell_constraints[icell];
+            let feasible = constraints.iter().all(|&j| {
+                self.missing[j] >= k
+                    && self.missing[j] - k <= self.capacity * (self.unassigned[j] - 1)
+            });
+            if !feasible {
+                continue;
+            }
+            for &j in constraints {
+                self.unassigned[j] -= 1;
+                self.missing[j] -= k;
+            }
+            self.assignment[icell] = k;
+            let done = self.run(icell + 1, nmines + k);
+            for &j in constraints {
+                self.unassigned[j] += 1;
+                self.missing[j] += k;
+            }
+            if !done {
+                return false;
+            }
+        }
+        self.assignment[icell] = 0;
+        true
+    }
+}
+
+fn convolve(a: &[BigUint], b: &[BigUint]) -> Vec<BigUint> {
+    let mut out = vec![BigUint::zero(); a.len() + b.len() - 1];
+    for (i, x) in a.iter().enumerate().filter(|(_, x)| !x.is_zero()) {
+        for (j, y) in b.iter().enumerate() {
+            out[i + j] += x * y;
+        }
+    }
+    out
+}
+
+/// `binomials(n)[k]` is the number of ways to choose `k` among `n`.
+fn binomials(n: usize) -> Vec<BigUint> {
+    let mut out = Vec::with_capacity(n + 1);
+    let mut current = BigUint::one();
+    for k in 0..=n {
+        out.push(current.clone());
+        current = current * (n - k) / (k + 1);
+    }
+    out
+}
+
+fn ratio(num: &BigUint, den: &BigUint) -> f64 {
+    let shift = (den.bits() + 64).saturating_sub(num.bits());
+    let quotient = (num << shift) / den;
+    quotient.to_f64().unwrap_or(f64::NAN) * (-(shift as f64)).exp2()
+}
+
+/// Compute the exact probability of each hidden cell holding a mine.
+///
+/// Returns `None` if the visible clues and flags admit no mine layout, if
+/// the board has anti-mines, which are not handled, or if the frontier has
+/// too many layouts to enumerate them within [`MAX_SEARCH_NODES`].
+pub fn mine_probabilities(board: &Board) -> Option<MineProbabilities> {
+    let shape = *board.shape();
+    if shape.anti_mines > 0 {
+        return None;
+    }
+    let capacity = usize::from(shape.mines_per_cell);
+    let groups = split_groups(solver::constraints(board));
+    let mut budget = MAX_SEARCH_NODES;
+    let solutions = groups
+        .iter()
+        .map(|g| g.solve(capacity, &mut budget))
+        .collect::<Option<Vec<_>>>()?;
+
+    let n_frontier: usize = groups.iter().map(|g| g.cells.len()).sum();
+    let n_hidden = shape
+        .cells()
+        .filter(|&(ir, ic)| board.get(ir, ic).is_hidden())
+        .count();
+    let n_other = n_hidden - n_frontier;
+    let n_missing = board.nmines().checked_sub(board.nflagged())?;
+
+    // weight(k) is the number of ways to place the mines that are not in the
+    // frontier when the frontier holds k mines.
+    let other_binomials = binomials(capacity * n_other);
+    let weight = |k: usize| {
+        n_missing
+            .checked_sub(k)
+            .and_then(|m| other_binomials.get(m))
+            .cloned()
+            .unwrap_or_default()
+    };
+
+    let one = vec![BigUint::one()];
+    let total_dist = solutions
+        .iter()
+        .fold(one.clone(), |acc, s| convolve(&acc, &s.count));
+    let total: BigUint = total_dist
+        .iter()
+        .enumerate()
+        .map(|(k, n)| n * weight(k))
+        .sum();
+    if total.is_zero() {
+        return None;
+    }
+
+    let mut probs = vec![None; shape.ncells()];
+    for (ig, (group, sol)) in groups.iter().zip(&solutions).enumerate() {
+        let rest_dist = solutions
+            .iter()
+            .enumerate()
+            .filter(|&(jg, _)| jg != ig)
+            .fold(one.clone(), |acc, (_, s)| convolve(&acc, &s.count));
+        for (cell, cell_count) in group.cells.iter().zip(&sol.cell_count) {
+            let mine_dist = convolve(cell_count, &rest_dist);
+            let n_mine: BigUint = mine_dist
+                .iter()
+                .enumerate()
+                .map(|(k, n)| n * weight(k))
+                .sum();
+            probs[shape.idx(cell.0, cell.1)] = Some(ratio(&n_mine, &total));
+        }
+    }
+
+    if n_other > 0 {
+        // Layouts where a given cell away from the frontier is empty.
+        let empty_binomials = binomials(capacity * (n_other - 1));
+        let n_empty: BigUint = total_dist
+            .iter()
+            .enumerate()
+            .filter_map(|(k, n)| Some(n * empty_binomials.get(n_missing.checked_sub(k)?)?))
+            .sum();
+        let p_other = ratio(&(&total - n_empty), &total);
+        for (ir, ic) in shape.cells() {
+            let icell = shape.idx(ir, ic);
+            if probs[icell].is_none() && board.get(ir, ic).is_hidden() {
+                probs[icell] = Some(p_other);
+            }
+        }
+    }
+
+    Some(MineProbabilities { shape, probs })
+}
+
+/// Mine probabilities of the last board seen, computed again only once the
+/// player's view of the board changes.
+#[derive(Default)]
+pub struct ProbabilityCache {
+    view: Option<(Shape, usize, Vec<CellState>)>,
+    probabilities: Option<MineProbabilities>,
+}
+
+impl ProbabilityCache {
+    pub fn update(&mut self, board: &Board) {
+        let unchanged = self.view.as_ref().is_some_and(|(shape, nmines, state)| {
+            shape == board.shape() && *nmines == board.nmines() && state == board.cell_states()
+        });
+        if !unchanged {
+            self.probabilities = mine_probabilities(board);
+            self.view = Some((*board.shape(), board.nmines(), board.cell_states().to_vec()));
+        }
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn get(&self) -> Option<&MineProbabilities> {
+        self.probabilities.as_ref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MineField;
+
+    fn assert_close(p: Option<f64>, expected: f64) {
+        let p = p.unwrap();
+        assert!((p - expected).abs() < 1e-9, "{p} != {expected}");
+    }
+
+    #[test]
+    fn no_information() {
+        let board = Board::new(MineField::new(4, 5, [(0, 0), (1, 1), (2, 2), (3, 3)]).unwrap());
+        let probs = mine_probabilities(&board).unwrap();
+        assert_close(probs.get(2, 4), 0.2);
+    }
+
+    #[test]
+    fn fifty_fifty() {
+        let mut board = Board::new(MineField::new(2, 2, [(0, 1)]).unwrap());
+        board.reveal(0, 0);
+        board.reveal(1, 0);
+        let probs = mine_probabilities(&board).unwrap();
+        assert_close(probs.get(0, 1), 0.5);
+        assert_close(probs.get(1, 1), 0.5);
+        assert!(probs.get(0, 0).is_none());
+    }
+
+    #[test]
+    fn weighted_by_global_count() {
+        // The clues see (0, 1) and (1, 1) only, and four cells are away from
+        // the frontier. The 2 mines are one on the frontier and one of four
+        // elsewhere, so frontier cells are mines with probability 1/2 and
+        // others with probability 1/4.
+        let mut board = Board::new(MineField::new(2, 4, [(0, 1), (1, 3)]).unwrap());
+        board.reveal(0, 0);
+        board.reveal(1, 0);
+        let probs = mine_probabilities(&board).unwrap();
+        assert_close(probs.get(0, 1), 0.5);
+        assert_close(probs.get(1, 1), 0.5);
+        assert_close(probs.get(0, 3), 0.25);
+        assert_close(probs.get(1, 2), 0.25);
+    }
+
+    #[test]
+    fn multi_mine_cells() {
+        // Two mines in six slots, the cell holds one of them unless both are
+        // in the four slots of the other cells: 1 - 6/15.
+        let shape = Shape {
+            nrows: 2,
+            ncols: 2,
+            mines_per_cell: 2,
+            ..Shape::default()
+        };
+        let mut board = Board::new(MineField::with_shape(shape, [(0, 1), (0, 1)]).unwrap());
+        board.reveal(0, 0);
+        let probs = mine_probabilities(&board).unwrap();
+        assert_close(probs.get(1, 1), 0.6);
+        assert_close(probs.get(0, 1), 0.6);
+    }
+
+    #[test]
+    fn large_board_does_not_overflow() {
+        let mut rng = rand::thread_rng();
+        let shape = Shape::square(100, 100);
+        let field = MineField::with_rand_mines_avoiding(shape, 2000, 0, 0, &mut rng).unwrap();
+        let board = Board::new(field);
+        let probs = mine_probabilities(&board).unwrap();
+        assert_close(probs.get(50, 50), 0.2);
+    }
+
+    #[test]
+    fn large_frontier_gives_up() {
+        // Clues on the middle row see three cells above and three below,
+        // with many ways to split their mines between the two rows.
+        let ncols = 60;
+        let mines = (0..ncols).map(|ic| (2 * (ic % 2), ic));
+        let mut board = Board::new(MineField::new(3, ncols, mines).unwrap());
+        for ic in 0..ncols {
+            board.reveal(1, ic);
+        }
+        let start = std::time::Instant::now();
+        assert!(mine_probabilities(&board).is_none());
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+
+        let mut cache = ProbabilityCache::default();
+        cache.update(&board);
+        assert!(cache.get().is_none());
+        board.toggle_flag(0, 0);
+        board.toggle_flag(0, 2);
+        cache.update(&board);
+        assert!(cache.view.as_ref().unwrap().2[0] == CellState::Flagged(1));
+    }
+}
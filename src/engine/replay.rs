@@ -0,0 +1,252 @@
+//! Recording of the moves of a game, to play it back later.
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use super::{check_count, history::History, Board, CellState, Error, MineField};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Action {
+    Reveal(usize, usize),
+    ToggleFlag(usize, usize),
+    Chord(usize, usize),
+    FlagChord(usize, usize),
+    Undo,
+    Redo,
+    /// Question marks turned on or off during the game.
+    QuestionMarks(bool),
+}
+
+impl Action {
+    /// Apply the action to the board. [`Action::Undo`] and [`Action::Redo`]
+    /// need a [`History`] and are ignored here.
+    pub(super) fn apply(&self, board: &mut Board) {
+        match *self {
+            Self::Reveal(ir, ic) => {
+                board.reveal(ir, ic);
+            }
+            Self::ToggleFlag(ir, ic) => board.toggle_flag(ir, ic),
+            Self::Chord(ir, ic) => board.reveal_around_nb(ir, ic),
+            Self::FlagChord(ir, ic) => board.flag_around_nb(ir, ic),
+            Self::QuestionMarks(enabled) => board.set_question_marks(enabled),
+            Self::Undo | Self::Redo => {}
+        }
+    }
+
+    /// Cell the action is played on, if any.
+    fn cell(&self) -> Option<(usize, usize)> {
+        match *self {
+            Self::Reveal(ir, ic)
+            | Self::ToggleFlag(ir, ic)
+            | Self::Chord(ir, ic)
+            | Self::FlagChord(ir, ic) => Some((ir, ic)),
+            Self::Undo | Self::Redo | Self::QuestionMarks(_) => None,
+        }
+    }
+}
+
+/// An action and when it happened, counted from the first click.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub struct Move {
+    pub time: Duration,
+    pub action: Action,
+}
+
+/// A generated field and the moves played on it.
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(try_from = "SavedReplay")]
+pub struct Replay {
+    field: MineField,
+    moves: Vec<Move>,
+    /// State of the cells before the first move, empty if they were all
+    /// hidden.
+    #[serde(default)]
+    initial_state: Vec<CellState>,
+    #[serde(default)]
+    question_marks: bool,
+    #[serde(default)]
+    clicks: usize,
+}
+
+/// Saved [`Replay`], checked against its field on load.
+#[derive(Deserialize)]
+struct SavedReplay {
+    field: MineField,
+    moves: Vec<Move>,
+    #[serde(default)]
+    initial_state: Vec<CellState>,
+    #[serde(default)]
+    question_marks: bool,
+    #[serde(default)]
+    clicks: usize,
+}
+
+impl TryFrom<SavedReplay> for Replay {
+    type Error = Error;
+
+    fn try_from(saved: SavedReplay) -> Result<Self, Error> {
+        let shape = saved.field.shape;
+        if !saved.initial_state.is_empty() {
+            check_count(shape.ncells(), saved.initial_state.len())?;
+        }
+        let outside = saved
+            .moves
+            .iter()
+            .filter_map(|mv| mv.action.cell())
+            .find(|&(ir, ic)| !shape.contains(ir, ic));
+        if let Some((ir, ic)) = outside {
+            return Err(Error::OutOfBounds(ir, ic));
+        }
+        Ok(Self {
+            field: saved.field,
+            moves: saved.moves,
+            initial_state: saved.initial_state,
+            question_marks: saved.question_marks,
+            clicks: saved.clicks,
+        })
+    }
+}
+
+impl Replay {
+    pub fn new(field: MineField) -> Self {
+        Self {
+            field,
+            moves: Vec::new(),
+            initial_state: Vec::new(),
+            question_marks: false,
+            clicks: 0,
+        }
+    }
+
+    /// Replay of a game starting from the given board, which may have some
+    /// cells already flagged or revealed, such as an imported one.
+    pub fn starting_from(board: &Board) -> Self {
+        let all_hidden = board.state.iter().all(|&s| s == CellState::Hidden);
+        Self {
+            field: board.field.clone(),
+            moves: Vec::new(),
+            initial_state: if all_hidden {
+                Vec::new()
+            } else {
+                board.state.clone()
+            },
+            question_marks: board.question_marks,
+            clicks: 0,
+        }
+    }
+
+    /// Record an action, given whether it changed the board.
+    pub fn record(&mut self, time: Duration, action: Action, changed: bool) {
+        self.moves.push(Move { time, action });
+        if changed && !matches!(action, Action::Undo | Action::Redo) {
+            self.clicks += 1;
+        }
+    }
+
+    /// Number of reveals, flags and chords that changed the board, undoing
+    /// and redoing not counting as clicks.
+    pub fn clicks(&self) -> usize {
+        self.clicks
+    }
+
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Time of the last move.
+    pub fn duration(&self) -> Duration {
+        self.moves.last().map_or(Duration::ZERO, |m| m.time)
+    }
+
+    /// Board before any move.
+    pub fn initial_board(&self) -> Board {
+        let mut board = if self.initial_state.is_empty() {
+            Board::new(self.field.clone())
+        } else {
+            Board::with_state(self.field.clone(), self.initial_state.clone())
+        };
+        board.set_question_marks(self.question_marks);
+        board
+    }
+
+    /// Board after all the moves played up to the given time.
+    pub fn board_at(&self, time: Duration) -> Board {
+        let mut board = self.initial_board();
+        let mut history = History::default();
+        for mv in self.moves.iter().take_while(|m| m.time <= time) {
+            history.play(&mut board, mv.action);
+        }
+        board
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::{Cell, CellState};
+
+    #[test]
+    fn replay_board_at() {
+        let mut replay = Replay::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        replay.record(Duration::ZERO, Action::Reveal(1, 1), true);
+        replay.record(Duration::from_secs(1), Action::ToggleFlag(2, 2), true);
+        replay.record(Duration::from_secs(2), Action::Chord(1, 1), true);
+        assert_eq!(replay.duration(), Duration::from_secs(2));
+
+        let board = replay.board_at(Duration::from_millis(500));
+        assert!(matches!(board.get(2, 2), CellState::Hidden));
+        assert!(matches!(board.get(0, 0), CellState::Hidden));
+
+        let board = replay.board_at(Duration::from_secs(2));
+        assert!(matches!(board.get(2, 2), CellState::Flagged(1)));
+        assert!(matches!(board.get(0, 0), CellState::Visible(Cell::Clear)));
+    }
+
+    #[test]
+    fn replay_question_marks() {
+        let mut replay = Replay::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        replay.record(Duration::ZERO, Action::QuestionMarks(true), false);
+        replay.record(Duration::ZERO, Action::ToggleFlag(2, 2), true);
+        replay.record(Duration::ZERO, Action::ToggleFlag(2, 2), true);
+        replay.record(Duration::ZERO, Action::QuestionMarks(false), false);
+        replay.record(Duration::ZERO, Action::ToggleFlag(0, 0), true);
+        replay.record(Duration::ZERO, Action::ToggleFlag(0, 0), true);
+        let board = replay.board_at(Duration::ZERO);
+        assert!(matches!(board.get(2, 2), CellState::Questioned));
+        assert!(matches!(board.get(0, 0), CellState::Hidden));
+        assert!(!board.question_marks());
+    }
+
+    #[test]
+    fn saved_replay_is_checked() {
+        let mut replay = Replay::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        replay.record(Duration::ZERO, Action::Reveal(1, 1), true);
+        let text = ron::to_string(&replay).unwrap();
+        assert!(ron::from_str::<Replay>(&text).is_ok());
+        let text = text.replace("Reveal(1,1)", "Reveal(1,5)");
+        assert!(ron::from_str::<Replay>(&text).is_err());
+    }
+
+    #[test]
+    fn replay_clicks() {
+        let mut board = Board::new(MineField::new(5, 5, [(2, 2)]).unwrap());
+        let mut replay = Replay::starting_from(&board);
+        let mut history = History::default();
+        let actions = [
+            Action::Reveal(1, 1),
+            Action::Reveal(1, 1),
+            Action::ToggleFlag(2, 2),
+            Action::Undo,
+            Action::Redo,
+            Action::Chord(1, 1),
+            Action::Chord(1, 1),
+        ];
+        for action in actions {
+            let changed = history.play(&mut board, action);
+            replay.record(Duration::ZERO, action, changed);
+        }
+        assert_eq!(replay.moves().len(), 7);
+        assert_eq!(replay.clicks(), 3);
+    }
+}
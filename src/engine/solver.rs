@@ -0,0 +1,248 @@
+//! Deterministic deductions from the player's view of a [`Board`].
+//!
+//! Only what the player can see is used: visible cells, flags and the total
+//! number of mines. Flags are trusted to be correct.
+//!
+//! When cells can hold several mines, the cells deduced to be mines are the
+//! ones that are full, holding as many mines as they can. Boards with
+//! anti-mines are not handled: nothing is deduced from them.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use super::{Board, Cell, CellState, MineField, Outcome};
+
+/// Cells that are provably safe and provably full of mines.
+#[derive(Default, Debug)]
+pub struct Deductions {
+    pub safe: BTreeSet<(usize, usize)>,
+    pub mines: BTreeSet<(usize, usize)>,
+}
+
+impl Deductions {
+    pub fn is_empty(&self) -> bool {
+        self.safe.is_empty() && self.mines.is_empty()
+    }
+}
+
+/// Hidden cells around a clue, and how many mines are missing among them.
+pub(super) struct Constraint {
+    pub(super) cells: BTreeSet<(usize, usize)>,
+    pub(super) mines: usize,
+}
+
+impl Constraint {
+    fn apply_to(&self, capacity: usize, deductions: &mut Deductions) {
+        if self.mines == 0 {
+            deductions.safe.extend(&self.cells);
+        } else if self.mines == capacity * self.cells.len() {
+            deductions.mines.extend(&self.cells);
+        }
+    }
+}
+
+pub(super) fn constraints(board: &Board) -> Vec<Constraint> {
+    let shape = board.shape();
+    let mut constraints = Vec::new();
+    if shape.anti_mines > 0 {
+        return constraints;
+    }
+    for (irow, icol) in shape.cells() {
+        let CellState::Visible(Cell::Neighbouring(n_nb)) = board.get(irow, icol) else {
+            continue;
+        };
+        let mut cells = BTreeSet::new();
+        let mut n_flagged = 0;
+        for (ir, ic) in shape.neighbours(irow, icol) {
+            match board.get(ir, ic) {
+                CellState::Hidden | CellState::Questioned => {
+                    cells.insert((ir, ic));
+                }
+                CellState::Flagged(n) => n_flagged += usize::from(n),
+                CellState::AntiFlagged | CellState::Visible(_) => {}
+            }
+        }
+        if !cells.is_empty() {
+            let mines = (n_nb.max(0) as usize).saturating_sub(n_flagged);
+            constraints.push(Constraint { cells, mines });
+        }
+    }
+    constraints
+}
+
+/// Bound the number of mines in the overlap of two constraints to deduce
+/// the content of the cells that belong to only one of them.
+fn deduce_overlap(a: &Constraint, b: &Constraint, capacity: usize, deductions: &mut Deductions) {
+    let n_inter = a.cells.intersection(&b.cells).count();
+    let a_only: Vec<_> = a.cells.difference(&b.cells).copied().collect();
+    let b_only: Vec<_> = b.cells.difference(&a.cells).copied().collect();
+    let lo = (a.mines.saturating_sub(capacity * a_only.len()))
+        .max(b.mines.saturating_sub(capacity * b_only.len()));
+    let hi = (capacity * n_inter).min(a.mines).min(b.mines);
+    if lo > hi {
+        return;
+    }
+    for (c, only) in [(a, a_only), (b, b_only)] {
+        if only.is_empty() {
+            continue;
+        }
+        if c.mines - lo == 0 {
+            deductions.safe.extend(only);
+        } else if c.mines - hi == capacity * only.len() {
+            deductions.mines.extend(only);
+        }
+    }
+}
+
+/// Deduce safe cells and mines.
+///
+/// Each clue is first considered on its own, then every pair of clues that
+/// share hidden neighbours is considered together. Finally, the number of
+/// remaining mines is compared with the number of hidden cells.
+pub fn deduce(board: &Board) -> Deductions {
+    let mut deductions = Deductions::default();
+    if board.shape().anti_mines > 0 {
+        return deductions;
+    }
+    let constraints = constraints(board);
+    let capacity = usize::from(board.shape().mines_per_cell);
+
+    let mut by_cell: BTreeMap<(usize, usize), Vec<usize>> = BTreeMap::new();
+    for (i, constraint) in constraints.iter().enumerate() {
+        constraint.apply_to(capacity, &mut deductions);
+        for &cell in &constraint.cells {
+            by_cell.entry(cell).or_default().push(i);
+        }
+    }
+
+    for (i, a) in constraints.iter().enumerate() {
+        let overlapping: BTreeSet<_> = a
+            .cells
+            .iter()
+            .flat_map(|cell| &by_cell[cell])
+            .copied()
+            .filter(|&j| j > i)
+            .collect();
+        for j in overlapping {
+            deduce_overlap(a, &constraints[j], capacity, &mut deductions);
+        }
+    }
+
+    if deductions.is_empty() {
+        let hidden: Vec<_> = board
+            .shape()
+            .cells()
+            .filter(|&(ir, ic)| board.get(ir, ic).is_hidden())
+            .collect();
+        let global = Constraint {
+            mines: board.nmines().saturating_sub(board.nflagged()),
+            cells: hidden.into_iter().collect(),
+        };
+        global.apply_to(capacity, &mut deductions);
+    }
+
+    deductions
+}
+
+/// Whether the board can be cleared from the given first click without ever
+/// having to guess.
+pub fn is_solvable_from(field: MineField, irow: usize, icol: usize) -> bool {
+    let mut board = Board::new(field);
+    board.reveal(irow, icol);
+    play_deductions(&mut board);
+    matches!(board.outcome(), Outcome::Won)
+}
+
+/// Flag the deduced mines and reveal the deduced safe cells until the game
+/// is over or nothing more can be deduced.
+pub(super) fn play_deductions(board: &mut Board) {
+    while let Outcome::Ongoing = board.outcome() {
+        let deductions = deduce(board);
+        if deductions.is_empty() {
+            return;
+        }
+        for (ir, ic) in deductions.mines {
+            for _ in 0..board.shape().mines_per_cell {
+                board.toggle_flag(ir, ic);
+            }
+        }
+        for (ir, ic) in deductions.safe {
+            board.reveal(ir, ic);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Shape;
+
+    #[test]
+    fn single_cell_rules() {
+        let mut board = Board::new(MineField::new(3, 3, [(0, 2)]).unwrap());
+        board.reveal(0, 0);
+        let deductions = deduce(&board);
+        assert!(deductions.mines.contains(&(0, 2)));
+        assert!(deductions.safe.is_empty());
+
+        board.toggle_flag(0, 2);
+        let deductions = deduce(&board);
+        assert!(deductions.is_empty());
+    }
+
+    #[test]
+    fn overlap_rules() {
+        // 1-2 pattern along the edge: the cell only seen by the 2 is a mine
+        // and the cell only seen by the 1 is safe.
+        let mut board = Board::new(MineField::new(2, 4, [(0, 1), (0, 3)]).unwrap());
+        board.reveal(1, 0);
+        board.reveal(1, 1);
+        board.reveal(1, 2);
+        board.reveal(1, 3);
+        let deductions = deduce(&board);
+        assert!(deductions.safe.contains(&(0, 0)));
+        assert!(deductions.safe.contains(&(0, 2)));
+        assert!(deductions.mines.contains(&(0, 1)));
+        assert!(deductions.mines.contains(&(0, 3)));
+    }
+
+    #[test]
+    fn mine_count_rule() {
+        let mut board = Board::new(MineField::new(2, 2, [(0, 0)]).unwrap());
+        board.toggle_flag(0, 0);
+        let deductions = deduce(&board);
+        assert_eq!(deductions.safe.len(), 3);
+    }
+
+    #[test]
+    fn multi_mine_rules() {
+        let shape = Shape {
+            nrows: 1,
+            ncols: 3,
+            mines_per_cell: 2,
+            ..Shape::default()
+        };
+        let mut board = Board::new(MineField::with_shape(shape, [(0, 0), (0, 0)]).unwrap());
+        board.reveal(0, 2);
+        let deductions = deduce(&board);
+        assert!(deductions.mines.contains(&(0, 0)));
+
+        // A single mine next to the clue may be one of two in the cell.
+        let mut board = Board::new(MineField::with_shape(shape, [(0, 0)]).unwrap());
+        board.reveal(0, 2);
+        assert!(deduce(&board).is_empty());
+    }
+
+    #[test]
+    fn solvable_layouts() {
+        assert!(is_solvable_from(
+            MineField::new(3, 3, [(0, 2)]).unwrap(),
+            2,
+            0
+        ));
+        assert!(!is_solvable_from(
+            MineField::new(2, 2, [(0, 1)]).unwrap(),
+            1,
+            0
+        ));
+    }
+}
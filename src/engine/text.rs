@@ -0,0 +1,404 @@
+//! Plain-text boards, to paste in bug reports and tests.
+//!
+//! A [`MineField`] is written as a header line followed by one line per row,
+//! the rows of the layers following each other. The header reads
+//! `<rows>x<cols>`, or `<rows>x<cols>x<layers>` for boards with several
+//! layers, followed by the same variant tags as game codes: `hex`, `torus`,
+//! `k<mask>` with a decimal [`Kernel::mask`], and `m<n>` for cells holding up
+//! to `n` mines. Each cell of a row is one character:
+//!
+//! - `.` for a cell without mine,
+//! - `*` for a mine, or the number of mines if there are several,
+//! - `-` for an anti-mine.
+//!
+//! A [`Board`] is written as its minefield followed by an empty line and a
+//! second grid with the state of each cell:
+//!
+//! - `#` for a hidden cell,
+//! - `F` for a flag, or the number of flags if there are several,
+//! - `A` for an anti-mine flag,
+//! - `?` for a question mark,
+//! - `o` for a revealed cell.
+//!
+//! The state grid may be left out when parsing a board, all cells being
+//! hidden then.
+//!
+//! ```text
+//! 3x4
+//! *...
+//! ..-.
+//! ....
+//!
+//! F###
+//! oo##
+//! oo##
+//! ```
+
+use std::{fmt, iter::Peekable, str::FromStr};
+
+use super::{kernel::Kernel, Board, Cell, CellState, MineField, Shape, Topology};
+
+/// Error in a text board, with the line and column where it was found, both
+/// counted from 1.
+#[derive(Debug, PartialEq, Eq)]
+pub struct TextError {
+    pub line: usize,
+    pub column: usize,
+    pub reason: String,
+}
+
+impl TextError {
+    fn new(line: usize, column: usize, reason: impl Into<String>) -> Self {
+        Self {
+            line,
+            column,
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.reason
+        )
+    }
+}
+
+impl std::error::Error for TextError {}
+
+/// Lines of a text, with their numbers.
+type Lines<'a> = Peekable<std::iter::Zip<std::ops::RangeFrom<usize>, std::str::Lines<'a>>>;
+
+fn header(shape: &Shape) -> String {
+    let mut fields = vec![format!("{}x{}", shape.nrows, shape.ncols)];
+    if shape.depth > 1 {
+        fields[0] += &format!("x{}", shape.depth);
+    }
+    match shape.topology {
+        Topology::Square => {}
+        Topology::Hexagonal => fields.push("hex".to_owned()),
+        Topology::Toroidal => fields.push("torus".to_owned()),
+    }
+    if shape.kernel != Kernel::STANDARD {
+        fields.push(format!("k{}", shape.kernel.mask()));
+    }
+    if shape.mines_per_cell > 1 {
+        fields.push(format!("m{}", shape.mines_per_cell));
+    }
+    fields.join(" ")
+}
+
+/// Words of a line, with the column they start at.
+fn words(line: &str) -> impl Iterator<Item = (usize, &str)> {
+    line.split(' ')
+        .scan(1, |column, word| {
+            let start = *column;
+            *column += word.chars().count() + 1;
+            Some((start, word))
+        })
+        .filter(|(_, word)| !word.is_empty())
+}
+
+fn parse_header(iline: usize, line: &str) -> Result<Shape, TextError> {
+    let mut words = words(line);
+    let Some((_, size)) = words.next() else {
+        return Err(TextError::new(iline, 1, "missing board size"));
+    };
+    let dims = size
+        .split('x')
+        .map(|n| n.parse().ok().filter(|&n: &usize| n > 0))
+        .collect::<Option<Vec<_>>>();
+    let (nrows, ncols, depth) = match dims.as_deref() {
+        Some(&[nrows, ncols]) => (nrows, ncols, 1),
+        Some(&[nrows, ncols, depth]) => (nrows, ncols, depth),
+        _ => {
+            return Err(TextError::new(
+                iline,
+                1,
+                format!("invalid board size `{size}`"),
+            ))
+        }
+    };
+    let mut shape = Shape {
+        nrows,
+        ncols,
+        depth,
+        ..Default::default()
+    };
+    for (column, tag) in words {
+        let invalid = || TextError::new(iline, column, format!("invalid tag `{tag}`"));
+        match tag {
+            "hex" => shape.topology = Topology::Hexagonal,
+            "torus" => shape.topology = Topology::Toroidal,
+            _ if tag.starts_with('k') => {
+                shape.kernel = tag[1..]
+                    .parse()
+                    .ok()
+                    .and_then(Kernel::from_mask)
+                    .ok_or_else(invalid)?;
+            }
+            _ if tag.starts_with('m') => {
+                shape.mines_per_cell = tag[1..]
+                    .parse()
+                    .ok()
+                    .filter(|n| (1..=3).contains(n))
+                    .ok_or_else(invalid)?;
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    shape
+        .check()
+        .map_err(|e| TextError::new(iline, 1, e.to_string()))?;
+    Ok(shape)
+}
+
+/// Parse one grid of characters, with a line per row of the shape.
+fn parse_grid<T>(
+    lines: &mut Lines,
+    shape: &Shape,
+    parse: impl Fn(char) -> Option<T>,
+) -> Result<Vec<T>, TextError> {
+    // Headers may announce more cells than the text holds.
+    let mut grid = Vec::new();
+    let mut last_line = 0;
+    for _ in 0..shape.total_rows() {
+        let Some((iline, line)) = lines.next() else {
+            return Err(TextError::new(
+                last_line + 1,
+                1,
+                format!("expected {} rows", shape.total_rows()),
+            ));
+        };
+        last_line = iline;
+        let mut chars = line.trim_end().chars();
+        for icol in 0..shape.ncols {
+            let Some(c) = chars.next() else {
+                return Err(TextError::new(
+                    iline,
+                    icol + 1,
+                    format!("expected {} cells", shape.ncols),
+                ));
+            };
+            let value = parse(c)
+                .ok_or_else(|| TextError::new(iline, icol + 1, format!("unexpected `{c}`")))?;
+            grid.push(value);
+        }
+        if chars.next().is_some() {
+            return Err(TextError::new(
+                iline,
+                shape.ncols + 1,
+                format!("expected {} cells", shape.ncols),
+            ));
+        }
+    }
+    Ok(grid)
+}
+
+fn skip_blank_lines(lines: &mut Lines) {
+    while lines.next_if(|(_, line)| line.trim().is_empty()).is_some() {}
+}
+
+fn parse_field(lines: &mut Lines) -> Result<MineField, TextError> {
+    skip_blank_lines(lines);
+    let (iline, line) = lines
+        .next()
+        .ok_or_else(|| TextError::new(1, 1, "empty board"))?;
+    let shape = parse_header(iline, line)?;
+    let first_row = iline + 1;
+    let grid = parse_grid(lines, &shape, |c| match c {
+        '.' => Some(Some(0)),
+        '*' => Some(Some(1)),
+        '-' => Some(None),
+        _ => c.to_digit(10).filter(|&n| n > 1).map(|n| Some(n as u8)),
+    })?;
+    let mut mines = Vec::new();
+    let mut anti_mines = Vec::new();
+    for ((irow, icol), cell) in shape.cells().zip(grid) {
+        match cell {
+            Some(n) if n > shape.mines_per_cell => {
+                return Err(TextError::new(
+                    first_row + irow,
+                    icol + 1,
+                    format!("cells hold at most {} mines", shape.mines_per_cell),
+                ))
+            }
+            Some(n) => mines.extend(std::iter::repeat_n((irow, icol), n.into())),
+            None => anti_mines.push((irow, icol)),
+        }
+    }
+    MineField::with_anti_mines(shape, mines, anti_mines)
+        .map_err(|e| TextError::new(iline, 1, e.to_string()))
+}
+
+fn expect_end(lines: &mut Lines) -> Result<(), TextError> {
+    skip_blank_lines(lines);
+    match lines.next() {
+        Some((iline, _)) => Err(TextError::new(iline, 1, "unexpected line")),
+        None => Ok(()),
+    }
+}
+
+impl fmt::Display for MineField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", header(&self.shape))?;
+        for irow in 0..self.shape.total_rows() {
+            let row: String = (0..self.shape.ncols)
+                .map(|icol| match self.get(irow, icol) {
+                    Cell::Clear | Cell::Neighbouring(_) => '.',
+                    Cell::Mine(1) => '*',
+                    Cell::Mine(n) => char::from(b'0' + n),
+                    Cell::AntiMine => '-',
+                })
+                .collect();
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MineField {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = (1..).zip(s.lines()).peekable();
+        let field = parse_field(&mut lines)?;
+        expect_end(&mut lines)?;
+        Ok(field)
+    }
+}
+
+impl fmt::Display for Board {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shape = self.shape();
+        writeln!(f, "{}", self.field)?;
+        for irow in 0..shape.total_rows() {
+            let row: String = (0..shape.ncols)
+                .map(|icol| match self.get(irow, icol) {
+                    CellState::Hidden => '#',
+                    CellState::Flagged(1) => 'F',
+                    CellState::Flagged(n) => char::from(b'0' + n),
+                    CellState::AntiFlagged => 'A',
+                    CellState::Questioned => '?',
+                    CellState::Visible(_) => 'o',
+                })
+                .collect();
+            writeln!(f, "{row}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Board {
+    type Err = TextError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = (1..).zip(s.lines()).peekable();
+        let mut board = Board::new(parse_field(&mut lines)?);
+        skip_blank_lines(&mut lines);
+        let Some(&(first_row, _)) = lines.peek() else {
+            return Ok(board);
+        };
+        let shape = *board.shape();
+        let grid = parse_grid(&mut lines, &shape, |c| match c {
+            '#' => Some(CellState::Hidden),
+            'F' => Some(CellState::Flagged(1)),
+            'A' => Some(CellState::AntiFlagged),
+            '?' => Some(CellState::Questioned),
+            'o' => Some(CellState::Visible(Cell::Clear)),
+            _ => c
+                .to_digit(10)
+                .filter(|&n| n > 1)
+                .map(|n| CellState::Flagged(n as u8)),
+        })?;
+        for ((irow, icol), state) in shape.cells().zip(grid) {
+            let icell = shape.idx(irow, icol);
+            let state = match state {
+                CellState::Flagged(n) if n > shape.mines_per_cell => {
+                    return Err(TextError::new(
+                        first_row + irow,
+                        icol + 1,
+                        format!("cells hold at most {} flags", shape.mines_per_cell),
+                    ))
+                }
+                CellState::Visible(_) => CellState::Visible(board.field.get(irow, icol)),
+                state => state,
+            };
+            board.set(icell, state);
+        }
+        expect_end(&mut lines)?;
+        Ok(board)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOARD: &str = "\
+3x4x2 torus m2
+*...
+..-.
+2...
+....
+....
+...*
+
+F###
+oo##
+####
+####
+##A#
+2?##
+";
+
+    #[test]
+    fn board_roundtrip() {
+        let board: Board = BOARD.parse().unwrap();
+        let shape = board.shape();
+        assert_eq!((shape.nrows, shape.ncols, shape.depth), (3, 4, 2));
+        assert_eq!(shape.topology, Topology::Toroidal);
+        assert_eq!(shape.anti_mines, 1);
+        assert_eq!(board.nmines(), 4);
+        assert!(matches!(board.field().get(2, 0), Cell::Mine(2)));
+        assert!(matches!(
+            board.get(1, 1),
+            CellState::Visible(Cell::Neighbouring(_))
+        ));
+        assert!(matches!(board.get(5, 0), CellState::Flagged(2)));
+        assert_eq!(board.to_string(), BOARD);
+
+        let field_text = BOARD.split("\n\n").next().unwrap().to_owned() + "\n";
+        let field: MineField = field_text.parse().unwrap();
+        assert_eq!(field.to_string(), field_text);
+        let board: Board = field_text.parse().unwrap();
+        assert_eq!(board.nflagged(), 0);
+    }
+
+    #[test]
+    fn error_positions() {
+        let error = |text: &str| text.parse::<Board>().err().unwrap();
+        assert_eq!(error("3x0\n").line, 1);
+        let e = error("2x2 hex cube\n..\n..\n");
+        assert_eq!((e.line, e.column), (1, 9));
+        let e = error("2x2\n..\n.x\n");
+        assert_eq!((e.line, e.column), (3, 2));
+        let e = error("2x2\n..\n...\n");
+        assert_eq!((e.line, e.column), (3, 3));
+        let e = error("2x2\n*.\n2.\n");
+        assert_eq!((e.line, e.column), (3, 1));
+        let e = error("2x2\n..\n..\n\n##\n");
+        assert_eq!((e.line, e.column), (6, 1));
+        let e = error("2x2\n..\n..\n\n##\n#o\n#\n");
+        assert_eq!((e.line, e.column), (7, 1));
+        assert_eq!(e.to_string(), "line 7, column 1: unexpected line");
+        assert_eq!(error("99999999999x99999999999\n").line, 1);
+        let e = error("100000x100000\n");
+        assert!(e
+            .to_string()
+            .ends_with("boards are limited to 268435456 cells"));
+    }
+}
@@ -1,18 +1,36 @@
-mod engine;
+mod bindings;
+mod endless_view;
+pub mod engine;
+mod stats;
 mod ui_objs;
+mod viewer;
 
 use std::{
-    mem,
+    collections::BTreeSet,
+    fs, mem,
     time::{Duration, Instant},
 };
 
 use eframe::{
     egui::{self, Button, RichText},
-    epaint::Vec2,
+    epaint::{Color32, Vec2},
 };
+use serde::{Deserialize, Serialize};
 
-use engine::{Board, Cell, CellState, MineField, Outcome, Shape};
-use ui_objs::{theme_picker, CellButton, ColorTheme};
+use bindings::{Bindings, CellCommand, Clicks, KeyCommand};
+use endless_view::EndlessView;
+use engine::{
+    code::GameCode,
+    history::History,
+    probability,
+    replay::{Action, Replay},
+    solver,
+    text::TextError,
+    Board, Cell, CellState, Outcome, Shape, Topology,
+};
+use stats::{Config, Stats};
+use ui_objs::{kernel_picker, theme_picker, CellButton, ColorTheme, HexLayout};
+use viewer::ReplayViewer;
 
 fn format_duration(duration: Duration) -> String {
     let seconds = duration.as_secs();
@@ -26,100 +44,338 @@ fn format_duration(duration: Duration) -> String {
     }
 }
 
+/// Storage keys of the parts of the app state restored on their own.
+const STATS_KEY: &str = "stats";
+const BINDINGS_KEY: &str = "bindings";
+
+/// Time added to the displayed and recorded time of a game for each hint.
+/// The clock of the replay is left alone, so that moves keep their times.
+const HINT_PENALTY: Duration = Duration::from_secs(10);
+
+/// Largest rows, columns and layers of the boards, every cell of which is
+/// drawn each frame.
+const MAX_ROWS: usize = 100;
+const MAX_COLS: usize = 200;
+const MAX_DEPTH: usize = 5;
+
+/// Check that the window can draw the board.
+fn check_drawable(shape: &Shape) -> Result<(), String> {
+    if shape.nrows > MAX_ROWS || shape.ncols > MAX_COLS || shape.depth > MAX_DEPTH {
+        Err(format!(
+            "boards are limited to {MAX_ROWS} rows, {MAX_COLS} columns and {MAX_DEPTH} layers"
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Rows, columns and mines of the preset boards.
+const PRESETS: [(usize, usize, usize); 3] = [(8, 8, 10), (16, 16, 40), (16, 32, 100)];
+
+#[derive(Copy, Clone)]
+enum Hint {
+    Safe(usize, usize),
+    Mine(usize, usize),
+    LowestRisk(usize, usize),
+}
+
+impl Hint {
+    fn find(board: &Board) -> Option<Self> {
+        let deductions = solver::deduce(board);
+        if let Some(&(ir, ic)) = deductions.safe.first() {
+            Some(Self::Safe(ir, ic))
+        } else if let Some(&(ir, ic)) = deductions.mines.first() {
+            Some(Self::Mine(ir, ic))
+        } else {
+            let ((ir, ic), _) = probability::mine_probabilities(board)?.safest()?;
+            Some(Self::LowestRisk(ir, ic))
+        }
+    }
+
+    fn cell(&self) -> (usize, usize) {
+        match *self {
+            Self::Safe(ir, ic) | Self::Mine(ir, ic) | Self::LowestRisk(ir, ic) => (ir, ic),
+        }
+    }
+
+    fn color_at(&self, irow: usize, icol: usize) -> Option<Color32> {
+        match *self {
+            Self::Safe(ir, ic) if (ir, ic) == (irow, icol) => Some(Color32::GREEN),
+            Self::Mine(ir, ic) if (ir, ic) == (irow, icol) => Some(Color32::RED),
+            Self::LowestRisk(ir, ic) if (ir, ic) == (irow, icol) => Some(Color32::YELLOW),
+            _ => None,
+        }
+    }
+}
+
+/// Clock of an ongoing game.
+///
+/// It is saved as the elapsed time since an `Instant` is only meaningful in
+/// the process that created it.
+#[derive(Copy, Clone, Serialize, Deserialize)]
+#[serde(from = "Duration", into = "Duration")]
+struct Timer {
+    start: Instant,
+    /// Time played before `start`, in an earlier session or before a lost
+    /// game was resumed.
+    offset: Duration,
+}
+
+impl Timer {
+    fn start() -> Self {
+        Self::from(Duration::ZERO)
+    }
+
+    fn elapsed(&self) -> Duration {
+        self.offset + self.start.elapsed()
+    }
+}
+
+impl From<Duration> for Timer {
+    fn from(elapsed: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            offset: elapsed,
+        }
+    }
+}
+
+impl From<Timer> for Duration {
+    fn from(timer: Timer) -> Self {
+        timer.elapsed()
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 enum BoardState {
     Waiting(Shape, usize),
-    Initialized(Board, Instant),
-    Won(Board, Duration),
-    Lost(Board),
+    Initialized(Board, Timer, Replay, History),
+    Won(Board, Duration, Replay),
+    Lost(Board, Duration, Replay, History),
+}
+
+/// Game written to file by the Save button.
+#[derive(Serialize, Deserialize)]
+struct SavedGame {
+    board: BoardState,
+    hints_used: usize,
+    practice: bool,
+    stats_recorded: bool,
+    code: Option<GameCode>,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct MineHunterApp {
     board: BoardState,
     theme: ColorTheme,
+    no_guess: bool,
+    /// Whether flags cycle through a question mark, from the next game on.
+    question_marks: bool,
+    show_probabilities: bool,
+    #[serde(skip)]
+    probabilities: probability::ProbabilityCache,
+    #[serde(skip)]
+    hint: Option<Hint>,
+    hints_used: usize,
+    practice: bool,
+    stats_recorded: bool,
+    code: Option<GameCode>,
+    #[serde(skip)]
+    code_input: String,
+    /// Code parsed from the input, updated when the input changes.
+    #[serde(skip)]
+    code_parsed: Option<GameCode>,
+    save_path: String,
+    #[serde(skip)]
+    file_status: Option<String>,
+    /// Board pasted for import, or the last exported one.
+    #[serde(skip)]
+    board_text: String,
+    #[serde(skip)]
+    viewer: Option<ReplayViewer>,
+    /// Saved under [`STATS_KEY`], to survive a board that fails to load.
+    #[serde(skip)]
+    stats: Stats,
+    #[serde(skip)]
+    show_stats: bool,
+    /// Saved under [`BINDINGS_KEY`].
+    #[serde(skip)]
+    bindings: Bindings,
+    #[serde(skip)]
+    show_bindings: bool,
+    /// Cell played with the keyboard.
+    #[serde(skip)]
+    cursor: Option<(usize, usize)>,
+    /// Whether the cursor moved and its cell should take the focus.
+    #[serde(skip)]
+    focus_cursor: bool,
+    /// Whether the cell of the cursor had the focus in the last frame.
+    #[serde(skip)]
+    cursor_focused: bool,
+    /// Layer shown on boards with several layers.
+    #[serde(skip)]
+    layer: usize,
+    #[serde(skip)]
+    endless: Option<EndlessView>,
+    /// Most cells cleared in an endless game.
+    best_endless: usize,
 }
 
 impl BoardState {
     fn shape(&self) -> &Shape {
         match self {
             Self::Waiting(shape, _) => shape,
-            Self::Initialized(board, _) => board.shape(),
-            Self::Won(board, _) => board.shape(),
-            Self::Lost(board) => board.shape(),
+            Self::Initialized(board, ..) => board.shape(),
+            Self::Won(board, ..) => board.shape(),
+            Self::Lost(board, ..) => board.shape(),
         }
     }
 
     fn nmines(&self) -> usize {
         match self {
             Self::Waiting(_, nmines) => *nmines,
-            Self::Initialized(b, _) | Self::Won(b, _) | Self::Lost(b) => b.nmines(),
+            Self::Initialized(b, ..) | Self::Won(b, ..) | Self::Lost(b, ..) => b.nmines(),
         }
     }
 
     fn get(&self, irow: usize, icol: usize) -> CellState {
         match self {
             Self::Waiting(..) => CellState::Hidden,
-            Self::Initialized(board, _) => board.get(irow, icol),
-            Self::Won(board, _) => board.get(irow, icol),
-            Self::Lost(board) => board.get(irow, icol),
+            Self::Initialized(board, ..) => board.get(irow, icol),
+            Self::Won(board, ..) => board.get(irow, icol),
+            Self::Lost(board, ..) => board.get(irow, icol),
         }
     }
 
-    fn reveal(&mut self, irow: usize, icol: usize) {
+    fn board(&self) -> Option<&Board> {
         match self {
-            Self::Waiting(shape, nmines) => {
-                let mut board = Board::new(MineField::with_rand_mines_avoiding(
-                    shape.nrows,
-                    shape.ncols,
-                    *nmines,
-                    irow,
-                    icol,
-                ));
-                board.reveal(irow, icol);
-                *self = Self::Initialized(board, Instant::now());
+            Self::Waiting(..) => None,
+            Self::Initialized(board, ..) | Self::Won(board, ..) | Self::Lost(board, ..) => {
+                Some(board)
             }
-            Self::Initialized(board, _) => {
-                board.reveal(irow, icol);
+        }
+    }
+
+    fn replay(&self) -> Option<&Replay> {
+        match self {
+            Self::Waiting(..) => None,
+            Self::Initialized(_, _, replay, _)
+            | Self::Won(_, _, replay)
+            | Self::Lost(_, _, replay, _) => Some(replay),
+        }
+    }
+
+    /// Apply and record an action of the player.
+    fn play(&mut self, action: Action) {
+        if let Self::Initialized(board, timer, replay, history) = self {
+            let changed = history.play(board, action);
+            replay.record(timer.elapsed(), action, changed);
+        }
+    }
+
+    /// Whether the undo history only touches cells of the board, which a
+    /// hand-edited save may break.
+    fn history_fits(&self) -> bool {
+        match self {
+            Self::Initialized(board, .., history) | Self::Lost(board, .., history) => {
+                history.fits(board)
             }
-            Self::Won(_, _) | Self::Lost(_) => {}
+            Self::Waiting(..) | Self::Won(..) => true,
+        }
+    }
+
+    fn can_undo(&self) -> bool {
+        match self {
+            Self::Initialized(.., history) | Self::Lost(.., history) => history.can_undo(),
+            Self::Waiting(..) | Self::Won(..) => false,
         }
     }
 
+    fn can_redo(&self) -> bool {
+        match self {
+            Self::Initialized(.., history) => history.can_redo(),
+            Self::Waiting(..) | Self::Won(..) | Self::Lost(..) => false,
+        }
+    }
+
+    /// Turn question marks on or off for the game being played, recording
+    /// the switch so that its replay toggles flags the same way.
+    fn set_question_marks(&mut self, enabled: bool) {
+        if matches!(self, Self::Initialized(..)) {
+            self.play(Action::QuestionMarks(enabled));
+        }
+    }
+
+    /// Undo the last action, resuming the game if it was lost.
+    fn undo(&mut self) {
+        if let Self::Lost(board, time, replay, history) = self {
+            *self = Self::Initialized(
+                mem::take(board),
+                Timer::from(*time),
+                mem::take(replay),
+                mem::take(history),
+            );
+        }
+        self.play(Action::Undo);
+    }
+
+    fn redo(&mut self) {
+        self.play(Action::Redo);
+    }
+
+    fn reveal(&mut self, irow: usize, icol: usize) {
+        self.play(Action::Reveal(irow, icol));
+    }
+
     fn reveal_around_nb(&mut self, irow: usize, icol: usize) {
-        if let CellState::Visible(Cell::Neighbouring(n_nb)) = self.get(irow, icol) {
-            let n_flagged = self
-                .shape()
-                .neighbours(irow, icol)
-                .filter(|&(ir, ic)| matches!(self.get(ir, ic), CellState::Flagged))
-                .count();
-            if n_flagged == n_nb.into() {
-                for (ir, ic) in self.shape().neighbours(irow, icol) {
-                    if matches!(self.get(ir, ic), CellState::Hidden) {
-                        self.reveal(ir, ic);
-                    }
-                }
-            }
+        if let CellState::Visible(Cell::Neighbouring(_)) = self.get(irow, icol) {
+            self.play(Action::Chord(irow, icol));
+        }
+    }
+
+    fn hint(&self) -> Option<Hint> {
+        if let Self::Initialized(board, ..) = self {
+            Hint::find(board)
+        } else {
+            None
         }
     }
 
     fn toggle_flag(&mut self, irow: usize, icol: usize) {
-        if let Self::Initialized(board, _) = self {
-            board.toggle_flag(irow, icol);
+        self.play(Action::ToggleFlag(irow, icol));
+    }
+
+    fn flag_around_nb(&mut self, irow: usize, icol: usize) {
+        if let CellState::Visible(Cell::Neighbouring(_)) = self.get(irow, icol) {
+            self.play(Action::FlagChord(irow, icol));
         }
     }
 
     fn update_win_lost(&mut self) {
-        if let Self::Initialized(board, start_time) = self {
+        if let Self::Initialized(board, timer, replay, history) = self {
             match board.outcome() {
                 Outcome::Won => {
                     for (ir, ic) in board.shape().cells() {
-                        if let CellState::Hidden = board.get(ir, ic) {
-                            board.toggle_flag(ir, ic);
+                        if board.get(ir, ic).is_hidden() {
+                            let anti = matches!(board.field().get(ir, ic), Cell::AntiMine);
+                            while board.get(ir, ic).is_hidden() {
+                                board.toggle_flag(ir, ic);
+                            }
+                            while anti && board.get(ir, ic) != CellState::AntiFlagged {
+                                board.toggle_flag(ir, ic);
+                            }
                         }
                     }
-                    *self = Self::Won(mem::take(board), Instant::now() - *start_time);
+                    *self = Self::Won(mem::take(board), timer.elapsed(), mem::take(replay));
                 }
                 Outcome::Lost => {
-                    *self = Self::Lost(mem::take(board));
+                    *self = Self::Lost(
+                        mem::take(board),
+                        timer.elapsed(),
+                        mem::take(replay),
+                        mem::take(history),
+                    );
                 }
                 Outcome::Ongoing => {}
             }
@@ -127,72 +383,545 @@ impl BoardState {
     }
 }
 
-impl MineHunterApp {
-    pub fn new(_cc: &::eframe::CreationContext<'_>) -> Self {
+impl Default for MineHunterApp {
+    fn default() -> Self {
         Self {
-            board: BoardState::Waiting(
-                Shape {
-                    nrows: 16,
-                    ncols: 16,
-                },
-                40,
-            ),
+            board: BoardState::Waiting(Shape::square(16, 16), 40),
             theme: ColorTheme::Blue,
+            no_guess: false,
+            question_marks: false,
+            show_probabilities: false,
+            probabilities: Default::default(),
+            hint: None,
+            hints_used: 0,
+            practice: false,
+            stats_recorded: false,
+            code: None,
+            code_input: String::new(),
+            code_parsed: None,
+            save_path: "minehunter-save.ron".to_owned(),
+            file_status: None,
+            board_text: String::new(),
+            viewer: None,
+            stats: Stats::default(),
+            show_stats: false,
+            bindings: Bindings::default(),
+            show_bindings: false,
+            cursor: None,
+            focus_cursor: false,
+            cursor_focused: false,
+            layer: 0,
+            endless: None,
+            best_endless: 0,
         }
     }
 }
 
-trait LaxClicked {
-    fn lax_clicked(&self) -> bool;
-    fn lax_r_clicked(&self) -> bool;
-}
+impl MineHunterApp {
+    pub fn new(cc: &::eframe::CreationContext<'_>) -> Self {
+        let Some(storage) = cc.storage else {
+            return Self::default();
+        };
+        let mut app: Self = ::eframe::get_value(storage, ::eframe::APP_KEY)
+            .filter(|app: &Self| {
+                check_drawable(app.board.shape()).is_ok() && app.board.history_fits()
+            })
+            .unwrap_or_default();
+        app.stats = ::eframe::get_value(storage, STATS_KEY).unwrap_or_default();
+        app.bindings = ::eframe::get_value(storage, BINDINGS_KEY).unwrap_or_default();
+        app
+    }
+
+    /// Start the game of the code, or report why its board can't be made.
+    fn start_game(&mut self, code: GameCode) {
+        if let Err(e) = check_drawable(&code.shape) {
+            self.file_status = Some(format!("Could not start: {e}"));
+            return;
+        }
+        let field = match code.generate() {
+            Ok((field, solvable)) => {
+                if code.no_guess && !solvable {
+                    self.file_status = Some(
+                        "No layout without guessing found, this one may need guesses".to_owned(),
+                    );
+                }
+                field
+            }
+            Err(e) => {
+                self.file_status = Some(format!("Could not start: {e}"));
+                return;
+            }
+        };
+        let mut board = Board::new(field);
+        board.set_question_marks(self.question_marks);
+        let replay = Replay::starting_from(&board);
+        let history = History::default();
+        self.board = BoardState::Initialized(board, Timer::start(), replay, history);
+        let (irow, icol) = code.first_click;
+        self.board.reveal(irow, icol);
+        self.code = Some(code);
+        self.hints_used = 0;
+        self.practice = false;
+        self.stats_recorded = false;
+    }
+
+    /// Time added by the hints of the game.
+    fn penalty(&self) -> Duration {
+        HINT_PENALTY * u32::try_from(self.hints_used).unwrap_or(u32::MAX)
+    }
+
+    /// Record the outcome of the game, once per game even if a loss is undone.
+    fn record_stats(&mut self) {
+        if self.stats_recorded {
+            return;
+        }
+        let config = Config {
+            shape: *self.board.shape(),
+            nmines: self.board.nmines(),
+        };
+        let assisted = self.hints_used > 0 || self.practice;
+        match self.board {
+            BoardState::Won(_, time, _) => {
+                let time = time + self.penalty();
+                self.stats.record_win(config, time, assisted);
+            }
+            BoardState::Lost(..) => self.stats.record_loss(config, assisted),
+            BoardState::Waiting(..) | BoardState::Initialized(..) => return,
+        }
+        self.stats_recorded = true;
+    }
+
+    fn save_to_file(&self) -> Result<(), String> {
+        let game = SavedGame {
+            board: self.board.clone(),
+            hints_used: self.hints_used,
+            practice: self.practice,
+            stats_recorded: self.stats_recorded,
+            code: self.code,
+        };
+        let text =
+            ron::ser::to_string_pretty(&game, Default::default()).map_err(|e| e.to_string())?;
+        fs::write(&self.save_path, text).map_err(|e| e.to_string())
+    }
+
+    fn load_from_file(&mut self) -> Result<(), String> {
+        let text = fs::read_to_string(&self.save_path).map_err(|e| e.to_string())?;
+        let game: SavedGame = ron::from_str(&text).map_err(|e| e.to_string())?;
+        check_drawable(game.board.shape())?;
+        if !game.board.history_fits() {
+            return Err("undo history doesn't match the board".to_owned());
+        }
+        self.board = game.board;
+        self.hints_used = game.hints_used;
+        self.practice = game.practice;
+        self.stats_recorded = game.stats_recorded;
+        self.code = game.code;
+        self.hint = None;
+        Ok(())
+    }
+
+    /// Start playing the pasted text board. Imported games count as practice.
+    fn import_board(&mut self) -> Result<(), String> {
+        let mut board: Board = self
+            .board_text
+            .parse()
+            .map_err(|e: TextError| e.to_string())?;
+        check_drawable(board.shape())?;
+        board.set_question_marks(self.question_marks);
+        let replay = Replay::starting_from(&board);
+        let history = History::default();
+        self.board = BoardState::Initialized(board, Timer::start(), replay, history);
+        self.board.update_win_lost();
+        self.code = None;
+        self.hints_used = 0;
+        self.practice = true;
+        self.stats_recorded = false;
+        self.hint = None;
+        Ok(())
+    }
+
+    fn displayed_cell(&self, irow: usize, icol: usize) -> CellState {
+        match &self.viewer {
+            Some(viewer) => viewer.board().get(irow, icol),
+            None => self.board.get(irow, icol),
+        }
+    }
+
+    fn cell_button(
+        &self,
+        irow: usize,
+        icol: usize,
+        scaling: f32,
+        probabilities: Option<&probability::MineProbabilities>,
+    ) -> CellButton {
+        let probability = probabilities.and_then(|p| p.get(irow, icol));
+        let highlight = self.hint.and_then(|h| h.color_at(irow, icol));
+        CellButton::new(self.displayed_cell(irow, icol), scaling, self.theme)
+            .with_probability(probability)
+            .with_highlight(highlight)
+    }
 
-impl LaxClicked for egui::Response {
-    fn lax_clicked(&self) -> bool {
-        self.clicked() || (self.drag_stopped_by(egui::PointerButton::Primary) && self.hovered())
+    fn restart(&mut self) {
+        self.board = BoardState::Waiting(*self.board.shape(), self.board.nmines());
     }
 
-    fn lax_r_clicked(&self) -> bool {
-        self.secondary_clicked()
-            || (self.drag_stopped_by(egui::PointerButton::Secondary) && self.hovered())
+    /// Wait for a game on a preset board, keeping the topology and clues.
+    fn pick_preset(&mut self, ip: usize) {
+        let (nrows, ncols, nmines) = PRESETS[ip];
+        let Shape {
+            topology, kernel, ..
+        } = *self.board.shape();
+        let shape = Shape {
+            nrows,
+            ncols,
+            topology,
+            kernel,
+            ..Shape::default()
+        };
+        self.board = BoardState::Waiting(shape, nmines);
+    }
+
+    fn on_key_command(&mut self, command: KeyCommand) {
+        match command {
+            KeyCommand::Move(drow, dcol) => self.move_cursor(drow, dcol),
+            KeyCommand::Cell(command) => {
+                if let Some((irow, icol)) = self.cursor {
+                    if self.board.shape().contains(irow, icol) {
+                        self.on_cell_command(irow, icol, command);
+                    }
+                }
+            }
+            KeyCommand::Restart => self.restart(),
+            KeyCommand::Preset(ip) => self.pick_preset(ip),
+            KeyCommand::NextTheme => self.theme = self.theme.next(),
+        }
+    }
+
+    /// Move the cursor within the layer shown, wrapping around on
+    /// wrap-around boards. A cursor outside the layer starts from its top
+    /// left cell.
+    fn move_cursor(&mut self, drow: isize, dcol: isize) {
+        let shape = *self.board.shape();
+        let first_row = self.layer.min(shape.depth - 1) * shape.nrows;
+        let (nrows, ncols) = (shape.nrows as isize, shape.ncols as isize);
+        let (row, col) = match self.cursor {
+            Some((irow, icol))
+                if (first_row..first_row + shape.nrows).contains(&irow) && icol < shape.ncols =>
+            {
+                let row = (irow - first_row) as isize + drow;
+                let col = icol as isize + dcol;
+                if shape.topology == Topology::Toroidal {
+                    (row.rem_euclid(nrows), col.rem_euclid(ncols))
+                } else {
+                    (row.clamp(0, nrows - 1), col.clamp(0, ncols - 1))
+                }
+            }
+            _ => (0, 0),
+        };
+        self.cursor = Some((first_row + row as usize, col as usize));
+        self.focus_cursor = true;
+    }
+
+    /// Give the focus to the cell of the cursor when it moved, and keep the
+    /// arrow keys for the cursor while it has the focus.
+    fn focus_cell(&mut self, ui: &egui::Ui, response: &egui::Response) {
+        if self.focus_cursor {
+            response.request_focus();
+            response.scroll_to_me(None);
+            self.focus_cursor = false;
+        }
+        if response.has_focus() {
+            self.cursor_focused = true;
+            let filter = egui::EventFilter {
+                horizontal_arrows: true,
+                vertical_arrows: true,
+                ..Default::default()
+            };
+            ui.memory_mut(|m| m.set_focus_lock_filter(response.id, filter));
+        }
+    }
+
+    fn on_cell_clicked(&mut self, irow: usize, icol: usize, clicks: Clicks) {
+        if let Some(command) = self.bindings.command(clicks) {
+            self.cursor = Some((irow, icol));
+            self.focus_cursor = true;
+            self.on_cell_command(irow, icol, command);
+        }
+    }
+
+    fn on_cell_command(&mut self, irow: usize, icol: usize, command: CellCommand) {
+        if self.viewer.is_some() {
+            return;
+        }
+        match (self.board.get(irow, icol), command) {
+            (CellState::Hidden | CellState::Questioned, CellCommand::Open) => {
+                if let BoardState::Waiting(shape, nmines) = self.board {
+                    self.start_game(GameCode {
+                        shape,
+                        nmines,
+                        first_click: (irow, icol),
+                        seed: rand::random(),
+                        no_guess: self.no_guess,
+                    });
+                } else {
+                    self.board.reveal(irow, icol);
+                }
+                self.hint = None;
+            }
+            (
+                CellState::Hidden
+                | CellState::Flagged(_)
+                | CellState::AntiFlagged
+                | CellState::Questioned,
+                CellCommand::Mark,
+            ) => {
+                self.board.toggle_flag(irow, icol);
+                self.hint = None;
+            }
+            (CellState::Visible(_), CellCommand::Chord) => {
+                self.board.reveal_around_nb(irow, icol);
+                self.hint = None;
+            }
+            (CellState::Visible(_), CellCommand::Open) if self.bindings.open_chords() => {
+                self.board.reveal_around_nb(irow, icol);
+                self.hint = None;
+            }
+            (CellState::Visible(_), CellCommand::Mark) if self.bindings.flag_chord => {
+                self.board.flag_around_nb(irow, icol);
+                self.hint = None;
+            }
+            _ => {}
+        }
+    }
+
+    /// Show one layer of the board in the given size, returning the hovered
+    /// cell.
+    ///
+    /// Previews of the layers next to the one being played are not
+    /// interactive, and outline the `marked` cells.
+    fn layer_board(
+        &mut self,
+        ui: &mut egui::Ui,
+        shape: Shape,
+        layer: usize,
+        size: Vec2,
+        probabilities: Option<&probability::MineProbabilities>,
+        marked: Option<&BTreeSet<(usize, usize)>>,
+    ) -> Option<(usize, usize)> {
+        match shape.topology {
+            Topology::Square | Topology::Toroidal => {
+                self.square_board(ui, shape, layer, size, probabilities, marked)
+            }
+            Topology::Hexagonal => self.hex_board(ui, shape, layer, size, probabilities, marked),
+        }
+    }
+
+    fn layer_cell_button(
+        &self,
+        irow: usize,
+        icol: usize,
+        scaling: f32,
+        probabilities: Option<&probability::MineProbabilities>,
+        marked: Option<&BTreeSet<(usize, usize)>>,
+    ) -> CellButton {
+        let btn = self.cell_button(irow, icol, scaling, probabilities);
+        if marked.is_some_and(|m| m.contains(&(irow, icol))) {
+            btn.with_highlight(Some(Color32::from_gray(200)))
+        } else {
+            btn
+        }
+    }
+
+    fn square_board(
+        &mut self,
+        ui: &mut egui::Ui,
+        shape: Shape,
+        layer: usize,
+        size: Vec2,
+        probabilities: Option<&probability::MineProbabilities>,
+        marked: Option<&BTreeSet<(usize, usize)>>,
+    ) -> Option<(usize, usize)> {
+        // Wrap-around boards show one ghost row or column past each edge.
+        let ghosts = usize::from(shape.topology == Topology::Toroidal);
+        let nrows_shown = shape.nrows + 2 * ghosts;
+        let ncols_shown = shape.ncols + 2 * ghosts;
+        let max_btn_width = size.x / ncols_shown as f32 - 2.0;
+        let max_btn_height = size.y / nrows_shown as f32 - 2.0;
+        let btn_size = max_btn_width.min(max_btn_height);
+        let mut scaling = btn_size / CellButton::base_size(ui);
+        if marked.is_none() {
+            scaling = scaling.max(1.0);
+        }
+        let mut hovered = None;
+        egui::Grid::new((layer, marked.is_some()))
+            .min_col_width(0.0)
+            .min_row_height(0.0)
+            .spacing((2.0, 2.0))
+            .show(ui, |ui| {
+                for jrow in 0..nrows_shown {
+                    for jcol in 0..ncols_shown {
+                        let irow = (jrow + shape.nrows - ghosts) % shape.nrows;
+                        let icol = (jcol + shape.ncols - ghosts) % shape.ncols;
+                        let ghost = (irow + ghosts, icol + ghosts) != (jrow, jcol);
+                        let irow = layer * shape.nrows + irow;
+                        let btn = self
+                            .layer_cell_button(irow, icol, scaling, probabilities, marked)
+                            .with_ghost(ghost);
+                        let response = ui.add(btn);
+                        if marked.is_none() && !ghost && self.cursor == Some((irow, icol)) {
+                            self.focus_cell(ui, &response);
+                        }
+                        if response.hovered() {
+                            hovered = Some((irow, icol));
+                        }
+                        if marked.is_none() {
+                            self.on_cell_clicked(irow, icol, Clicks::lax(&response));
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+        hovered
+    }
+
+    fn hex_board(
+        &mut self,
+        ui: &mut egui::Ui,
+        shape: Shape,
+        layer: usize,
+        size: Vec2,
+        probabilities: Option<&probability::MineProbabilities>,
+        marked: Option<&BTreeSet<(usize, usize)>>,
+    ) -> Option<(usize, usize)> {
+        let layout = HexLayout::fit(size, shape.nrows, shape.ncols);
+        let (rect, response) = ui.allocate_exact_size(layout.size(), egui::Sense::click_and_drag());
+        let layout = layout.with_origin(rect.min);
+        let scaling = 2.0 * layout.radius / CellButton::base_size(ui);
+        let first_row = layer * shape.nrows;
+        let hovered = response
+            .hover_pos()
+            .and_then(|pos| layout.cell_at(pos))
+            .map(|(irow, icol)| (first_row + irow, icol));
+        let painter = ui.painter_at(rect);
+        for irow in 0..shape.nrows {
+            for icol in 0..shape.ncols {
+                let cell = (first_row + irow, icol);
+                self.layer_cell_button(cell.0, cell.1, scaling, probabilities, marked)
+                    .paint_hex(
+                        &painter,
+                        layout.center(irow, icol),
+                        0.95 * layout.radius,
+                        hovered == Some(cell) || (marked.is_none() && self.cursor == Some(cell)),
+                    );
+            }
+        }
+        if let (Some((irow, icol)), None) = (hovered, marked) {
+            self.on_cell_clicked(irow, icol, Clicks::lax(&response));
+        }
+        hovered
     }
 }
 
 impl ::eframe::App for MineHunterApp {
+    fn save(&mut self, storage: &mut dyn ::eframe::Storage) {
+        ::eframe::set_value(storage, ::eframe::APP_KEY, self);
+        ::eframe::set_value(storage, STATS_KEY, &self.stats);
+        ::eframe::set_value(storage, BINDINGS_KEY, &self.bindings);
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut ::eframe::Frame) {
         ctx.request_repaint_after(Duration::from_millis(111));
+        // Keys play on the board unless another widget has the focus.
+        if self.endless.is_none() && (self.cursor_focused || !ctx.wants_keyboard_input()) {
+            for command in bindings::key_commands(ctx) {
+                self.on_key_command(command);
+            }
+        }
+        self.cursor_focused = false;
         egui::SidePanel::left("ctrl_panel").show(ctx, |ui| {
             ui.add_space(15.0);
-            let shape = self.board.shape();
+            let shape = *self.board.shape();
             let mut nrows = shape.nrows;
             let mut ncols = shape.ncols;
+            let mut topology = shape.topology;
+            let mut depth = shape.depth;
+            let mut kernel = shape.kernel;
+            let mut mines_per_cell = shape.mines_per_cell;
+            let mut anti_mines = shape.anti_mines;
             let mut nmines = self.board.nmines();
-            let nmines_min = shape.ncells() / 10;
-            let nmines_max = 2 * shape.ncells() / 5;
-            ui.add(egui::Slider::new(&mut nrows, 8..=30).text("Rows"));
-            ui.add(egui::Slider::new(&mut ncols, 8..=50).text("Cols"));
+            let capacity = shape.ncells() * usize::from(shape.mines_per_cell);
+            let nmines_min = capacity / 10;
+            let nmines_max = 2 * capacity / 5;
+            ui.add(egui::Slider::new(&mut nrows, 8..=MAX_ROWS).text("Rows"));
+            ui.add(egui::Slider::new(&mut ncols, 8..=MAX_COLS).text("Cols"));
+            ui.add(egui::Slider::new(&mut depth, 1..=MAX_DEPTH).text("Layers"));
+            ui.add(egui::Slider::new(&mut mines_per_cell, 1..=3).text("Mines per cell"));
             ui.add(egui::Slider::new(&mut nmines, nmines_min..=nmines_max).text("Mines"));
+            let anti_mines_max = shape.ncells() / 10;
+            ui.add(egui::Slider::new(&mut anti_mines, 0..=anti_mines_max).text("Anti-mines"));
+            ui.horizontal_wrapped(|ui| {
+                ui.radio_value(&mut topology, Topology::Square, "Square");
+                ui.radio_value(&mut topology, Topology::Hexagonal, "Hexagonal");
+                ui.radio_value(&mut topology, Topology::Toroidal, "Wrap-around");
+            });
+            if topology != Topology::Hexagonal {
+                kernel_picker(&mut kernel, self.theme, ui);
+            }
+            if shape.depth > 1 {
+                let mut layer = self.layer.min(shape.depth - 1) + 1;
+                ui.add(egui::Slider::new(&mut layer, 1..=shape.depth).text("Layer"));
+                self.layer = layer - 1;
+            }
+            ui.checkbox(&mut self.no_guess, "No guessing");
+            if ui
+                .checkbox(&mut self.question_marks, "Question marks")
+                .changed()
+            {
+                self.board.set_question_marks(self.question_marks);
+            }
 
             ui.add_space(15.0);
-            if !matches!(self.board, BoardState::Initialized(_, _)) {
-                if nrows != shape.nrows || ncols != shape.ncols {
-                    nmines = nrows * ncols / 5;
+            if !matches!(self.board, BoardState::Initialized(..)) {
+                let new_shape = Shape {
+                    nrows,
+                    ncols,
+                    topology,
+                    depth,
+                    kernel,
+                    mines_per_cell,
+                    anti_mines,
+                };
+                if new_shape.ncells() != shape.ncells()
+                    || new_shape.mines_per_cell != shape.mines_per_cell
+                {
+                    nmines = new_shape.ncells() * usize::from(mines_per_cell) / 5;
                 }
-                if nrows != shape.nrows || ncols != shape.ncols || nmines != self.board.nmines() {
-                    self.board = BoardState::Waiting(Shape { nrows, ncols }, nmines);
+                if new_shape != shape || nmines != self.board.nmines() {
+                    self.board = BoardState::Waiting(new_shape, nmines);
                 }
             }
 
             let msg: String = match &self.board {
-                BoardState::Won(_, _) => "Congratulations!".to_owned(),
-                BoardState::Lost(_) => "You lost...".to_owned(),
+                BoardState::Won(..) if self.practice => "Congratulations!\n(practice)".to_owned(),
+                BoardState::Won(..) if self.hints_used > 0 => {
+                    format!("Congratulations!\n(assisted, {} hints)", self.hints_used)
+                }
+                BoardState::Won(..) => "Congratulations!".to_owned(),
+                BoardState::Lost(..) => "You lost...".to_owned(),
                 BoardState::Waiting(..) => "Pick a cell".to_owned(),
-                BoardState::Initialized(board, _) => {
+                BoardState::Initialized(board, ..) if board.shape().anti_mines > 0 => format!(
+                    "Flagged: {} / {}\nAnti-flagged: {} / {}",
+                    board.nflagged(),
+                    board.nmines(),
+                    board.n_anti_flagged(),
+                    board.shape().anti_mines
+                ),
+                BoardState::Initialized(board, ..) => {
                     format!("Flagged: {} / {}", board.nflagged(), board.nmines())
                 }
             };
             let mut msg = RichText::new(msg).size(20.0);
-            if matches!(self.board, BoardState::Won(_, _)) {
+            if matches!(self.board, BoardState::Won(..)) {
                 msg = msg.color(self.theme.main_color());
             }
             ui.label(msg);
@@ -203,73 +932,245 @@ impl ::eframe::App for MineHunterApp {
             egui::Grid::new(1).show(ui, |ui| {
                 let btn = Button::new("Restart").min_size(btn_size);
                 if ui.add(btn).clicked() {
-                    self.board = BoardState::Waiting(*self.board.shape(), self.board.nmines());
+                    self.restart();
                 }
-                let presets = [(8, 8, 10), (16, 16, 40), (16, 32, 100)];
-                for (ip, (nrows, ncols, nmines)) in presets.into_iter().enumerate() {
+                for (ip, (nrows, ncols, nmines)) in PRESETS.into_iter().enumerate() {
                     let btn =
                         Button::new(format!("{nrows}x{ncols}\n{nmines} mines")).min_size(btn_size);
                     if ui.add(btn).clicked() {
-                        self.board = BoardState::Waiting(Shape { nrows, ncols }, nmines);
+                        self.pick_preset(ip);
                     }
                     if ip % 2 == 0 {
                         ui.end_row();
                     }
                 }
+                let btn = Button::new("Hint").min_size(btn_size);
+                let playing = matches!(self.board, BoardState::Initialized(..));
+                if ui.add_enabled(playing, btn).clicked() {
+                    self.hint = self.board.hint();
+                    if let Some(hint) = self.hint {
+                        self.hints_used += 1;
+                        self.layer = hint.cell().0 / self.board.shape().nrows;
+                    } else {
+                        self.file_status = Some("No hint available".to_owned());
+                    }
+                }
+                let btn = Button::new("Undo").min_size(btn_size);
+                if ui.add_enabled(self.board.can_undo(), btn).clicked() {
+                    self.board.undo();
+                    self.practice = true;
+                    self.hint = None;
+                }
+                ui.end_row();
+                let btn = Button::new("Redo").min_size(btn_size);
+                if ui.add_enabled(self.board.can_redo(), btn).clicked() {
+                    self.board.redo();
+                    self.hint = None;
+                }
+            });
+
+            ui.add_space(15.0);
+            ui.horizontal(|ui| {
+                if ui.button("Statistics").clicked() {
+                    self.show_stats = !self.show_stats;
+                }
+                if ui.button("Bindings").clicked() {
+                    self.show_bindings = !self.show_bindings;
+                }
             });
 
+            ui.add_space(15.0);
+            if let Some(endless) = &mut self.endless {
+                if endless.controls(ui, self.best_endless) {
+                    self.endless = None;
+                }
+            } else if ui.button("Endless mode").clicked() {
+                self.endless = Some(EndlessView::new());
+            }
+
             ui.add_space(15.0);
             theme_picker(&mut self.theme, ui);
+            ui.checkbox(&mut self.show_probabilities, "Show mine probabilities");
+            match &self.board {
+                BoardState::Initialized(board, ..) if self.show_probabilities => {
+                    self.probabilities.update(board);
+                    if self.probabilities.get().is_none() {
+                        ui.label("Mine probabilities unavailable");
+                    }
+                }
+                _ => self.probabilities.clear(),
+            }
 
             ui.add_space(15.0);
-            if let BoardState::Initialized(_, start_time) = self.board {
-                let time = Instant::now() - start_time;
+            if let BoardState::Initialized(_, timer, ..) = &self.board {
+                let time = timer.elapsed() + self.penalty();
                 let msg = RichText::new(format_duration(time)).size(20.0);
                 ui.label(msg);
-            } else if let BoardState::Won(_, time) = self.board {
+            } else if let BoardState::Won(board, time, replay) = &self.board {
+                let time = *time + self.penalty();
                 let msg = RichText::new(format_duration(time)).size(20.0);
                 ui.label(msg);
+                let three_bv = board.field().three_bv();
+                let n_clicks = replay.clicks().max(1);
+                ui.label(format!(
+                    "3BV: {three_bv}   3BV/s: {:.2}   Efficiency: {:.0}%",
+                    three_bv as f64 / time.as_secs_f64().max(1e-3),
+                    100.0 * three_bv as f64 / n_clicks as f64,
+                ));
+            }
+
+            ui.add_space(15.0);
+            if let (Some(code), false) = (self.code, matches!(self.board, BoardState::Waiting(..)))
+            {
+                ui.horizontal(|ui| {
+                    ui.label(format!("Game code: {code}"));
+                    if ui.small_button("Copy").clicked() {
+                        ui.output_mut(|o| o.copied_text = code.to_string());
+                    }
+                });
+            }
+            ui.horizontal(|ui| {
+                let response = ui.add(
+                    egui::TextEdit::singleline(&mut self.code_input)
+                        .hint_text("Paste a game code")
+                        .desired_width(ui.available_width() / 1.5),
+                );
+                if response.changed() {
+                    self.code_parsed = self.code_input.parse().ok();
+                }
+                if ui
+                    .add_enabled(self.code_parsed.is_some(), Button::new("Play"))
+                    .clicked()
+                {
+                    if let Some(code) = self.code_parsed {
+                        self.start_game(code);
+                    }
+                }
+            });
+
+            ui.add_space(15.0);
+            ui.add(egui::TextEdit::singleline(&mut self.save_path).hint_text("Save file"));
+            ui.horizontal(|ui| {
+                if ui.button("Save").clicked() {
+                    self.file_status = Some(match self.save_to_file() {
+                        Ok(()) => "Game saved".to_owned(),
+                        Err(e) => format!("Could not save: {e}"),
+                    });
+                }
+                if ui.button("Load").clicked() {
+                    self.file_status = Some(match self.load_from_file() {
+                        Ok(()) => "Game loaded".to_owned(),
+                        Err(e) => format!("Could not load: {e}"),
+                    });
+                }
+            });
+            ui.add(
+                egui::TextEdit::multiline(&mut self.board_text)
+                    .hint_text("Paste a text board")
+                    .font(egui::TextStyle::Monospace)
+                    .desired_rows(4),
+            );
+            ui.horizontal(|ui| {
+                if ui.button("Import").clicked() {
+                    self.file_status = Some(match self.import_board() {
+                        Ok(()) => "Board imported".to_owned(),
+                        Err(e) => format!("Could not import: {e}"),
+                    });
+                }
+                if ui
+                    .add_enabled(self.board.board().is_some(), Button::new("Export"))
+                    .clicked()
+                {
+                    if let Some(text) = self.board.board().map(Board::to_string) {
+                        ui.output_mut(|o| o.copied_text = text.clone());
+                        self.board_text = text;
+                        self.file_status = Some("Board copied".to_owned());
+                    }
+                }
+            });
+            if let Some(status) = &self.file_status {
+                ui.label(status);
+            }
+
+            ui.add_space(15.0);
+            if let Some(viewer) = &mut self.viewer {
+                if viewer.controls(ui) {
+                    self.viewer = None;
+                }
+            } else if matches!(self.board, BoardState::Won(..) | BoardState::Lost(..))
+                && ui.button("Watch replay").clicked()
+            {
+                self.viewer = self.board.replay().cloned().map(ReplayViewer::new);
             }
         });
+        if let Some(viewer) = &mut self.viewer {
+            viewer.advance();
+            ctx.request_repaint();
+        }
+        egui::Window::new("Statistics")
+            .open(&mut self.show_stats)
+            .show(ctx, |ui| self.stats.ui(ui));
+        egui::Window::new("Bindings")
+            .open(&mut self.show_bindings)
+            .show(ctx, |ui| self.bindings.ui(ui));
+        // Taken out for the time of the frame, the board being drawn by
+        // methods that borrow the whole app.
+        let probabilities = mem::take(&mut self.probabilities);
         egui::CentralPanel::default().show(ctx, |ui| {
-            let nrows = self.board.shape().nrows;
-            let ncols = self.board.shape().ncols;
-            let Vec2 {
-                x: width,
-                y: height,
-            } = ui.available_size();
-            let max_btn_width = width / ncols as f32 - 2.0;
-            let max_btn_height = height / nrows as f32 - 2.0;
-            let btn_size = max_btn_width.min(max_btn_height);
-            let scaling = (btn_size / CellButton::base_size(ui)).max(1.0);
-            egui::Grid::new(0)
-                .min_col_width(0.0)
-                .min_row_height(0.0)
-                .spacing((2.0, 2.0))
-                .show(ui, |ui| {
-                    for irow in 0..nrows {
-                        for icol in 0..ncols {
-                            let cell = self.board.get(irow, icol);
-                            let response = ui.add(CellButton::new(cell, scaling, self.theme));
-                            match cell {
-                                CellState::Hidden if response.lax_clicked() => {
-                                    self.board.reveal(irow, icol);
-                                }
-                                CellState::Hidden | CellState::Flagged
-                                    if response.lax_r_clicked() =>
-                                {
-                                    self.board.toggle_flag(irow, icol);
-                                }
-                                CellState::Visible(_) if response.lax_clicked() => {
-                                    self.board.reveal_around_nb(irow, icol);
-                                }
-                                _ => {}
-                            }
-                        }
-                        ui.end_row();
-                    }
+            if let Some(endless) = &mut self.endless {
+                endless.ui(ui, self.theme, &mut self.bindings);
+                self.best_endless = self.best_endless.max(endless.score());
+                return;
+            }
+            let shape = match &self.viewer {
+                Some(viewer) => *viewer.board().shape(),
+                None => *self.board.shape(),
+            };
+            let probabilities = probabilities.get();
+            let available = ui.available_size();
+            if shape.depth == 1 {
+                // Cells keep a minimum size, large boards are scrolled.
+                egui::ScrollArea::both().show(ui, |ui| {
+                    self.layer_board(ui, shape, 0, available, probabilities, None);
                 });
+                return;
+            }
+            // The layers around the played one are previewed below it, with
+            // the neighbours of the hovered cell outlined.
+            self.layer = self.layer.min(shape.depth - 1);
+            let layer = self.layer;
+            let main_size = Vec2::new(available.x, 0.7 * available.y);
+            let hovered = self.layer_board(ui, shape, layer, main_size, probabilities, None);
+            let marked: BTreeSet<_> = hovered
+                .into_iter()
+                .flat_map(|(irow, icol)| shape.neighbours(irow, icol))
+                .collect();
+            ui.separator();
+            let preview_size = Vec2::new(
+                0.5 * available.x - ui.spacing().item_spacing.x,
+                ui.available_height() - 2.0 * ui.spacing().interact_size.y,
+            );
+            let adjacent = [layer.checked_sub(1), Some(layer + 1)];
+            ui.horizontal_top(|ui| {
+                for l in adjacent.into_iter().flatten().filter(|&l| l < shape.depth) {
+                    ui.vertical(|ui| {
+                        ui.label(format!("Layer {}", l + 1));
+                        self.layer_board(ui, shape, l, preview_size, probabilities, Some(&marked));
+                    });
+                }
+            });
         });
+        self.probabilities = probabilities;
+        let was_playing = matches!(self.board, BoardState::Initialized(..));
         self.board.update_win_lost();
+        if was_playing {
+            self.record_stats();
+        }
+        if !matches!(self.board, BoardState::Initialized(..)) {
+            self.hint = None;
+        }
+        if !matches!(self.board, BoardState::Won(..) | BoardState::Lost(..)) {
+            self.viewer = None;
+        }
     }
 }
@@ -0,0 +1,195 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use eframe::egui;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    engine::{kernel::Kernel, Shape, Topology},
+    format_duration,
+};
+
+/// Board configuration that statistics are kept for.
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub(crate) struct Config {
+    pub(crate) shape: Shape,
+    pub(crate) nmines: usize,
+}
+
+/// Results of the games played with one configuration.
+///
+/// Assisted games, played with hints or undos, count as played, won or lost,
+/// but their times are left out of the best, average and median times.
+#[derive(Default, Clone, Serialize, Deserialize)]
+pub(crate) struct ConfigStats {
+    won: usize,
+    lost: usize,
+    assisted: usize,
+    win_times: Vec<Duration>,
+    current_streak: usize,
+    best_streak: usize,
+}
+
+impl ConfigStats {
+    fn record_win(&mut self, time: Duration, assisted: bool) {
+        self.won += 1;
+        if assisted {
+            self.assisted += 1;
+        } else {
+            self.win_times.push(time);
+        }
+        self.current_streak += 1;
+        self.best_streak = self.best_streak.max(self.current_streak);
+    }
+
+    fn record_loss(&mut self, assisted: bool) {
+        self.lost += 1;
+        if assisted {
+            self.assisted += 1;
+        }
+        self.current_streak = 0;
+    }
+
+    fn played(&self) -> usize {
+        self.won + self.lost
+    }
+
+    fn win_rate(&self) -> f64 {
+        if self.played() == 0 {
+            0.0
+        } else {
+            self.won as f64 / self.played() as f64
+        }
+    }
+
+    fn best_time(&self) -> Option<Duration> {
+        self.win_times.iter().min().copied()
+    }
+
+    fn average_time(&self) -> Option<Duration> {
+        let n = u32::try_from(self.win_times.len())
+            .ok()
+            .filter(|&n| n > 0)?;
+        Some(self.win_times.iter().sum::<Duration>() / n)
+    }
+
+    fn median_time(&self) -> Option<Duration> {
+        let mut times = self.win_times.clone();
+        times.sort();
+        let n = times.len();
+        match n {
+            0 => None,
+            _ if n % 2 == 1 => Some(times[n / 2]),
+            _ => Some((times[n / 2 - 1] + times[n / 2]) / 2),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Stats {
+    by_config: BTreeMap<Config, ConfigStats>,
+}
+
+impl Stats {
+    pub(crate) fn record_win(&mut self, config: Config, time: Duration, assisted: bool) {
+        self.by_config
+            .entry(config)
+            .or_default()
+            .record_win(time, assisted);
+    }
+
+    pub(crate) fn record_loss(&mut self, config: Config, assisted: bool) {
+        self.by_config
+            .entry(config)
+            .or_default()
+            .record_loss(assisted);
+    }
+
+    pub(crate) fn ui(&self, ui: &mut egui::Ui) {
+        if self.by_config.is_empty() {
+            ui.label("No game played yet");
+            return;
+        }
+        let fmt_time = |time: Option<Duration>| time.map_or("-".to_owned(), format_duration);
+        egui::Grid::new("stats").striped(true).show(ui, |ui| {
+            for header in [
+                "Board",
+                "Played",
+                "Won",
+                "Lost",
+                "Assisted",
+                "Win rate",
+                "Best",
+                "Average",
+                "Median",
+                "Streak",
+                "Best streak",
+            ] {
+                ui.strong(header);
+            }
+            ui.end_row();
+            for (Config { shape, nmines }, stats) in &self.by_config {
+                let topology = match shape.topology {
+                    Topology::Square => "",
+                    Topology::Hexagonal => " hex",
+                    Topology::Toroidal => " torus",
+                };
+                let layers = match shape.depth {
+                    1 => String::new(),
+                    depth => format!("x{depth}"),
+                };
+                let kernel =
+                    if shape.topology == Topology::Hexagonal || shape.kernel == Kernel::STANDARD {
+                        String::new()
+                    } else {
+                        let name = shape.kernel.name().unwrap_or("custom");
+                        format!(" {}", name.to_lowercase())
+                    };
+                let per_cell = match shape.mines_per_cell {
+                    1 => String::new(),
+                    n => format!(" (up to {n} per cell)"),
+                };
+                let anti = match shape.anti_mines {
+                    0 => String::new(),
+                    n => format!(", {n} anti-mines"),
+                };
+                ui.label(format!(
+                    "{}x{}{layers}{topology}{kernel}, {} mines{per_cell}{anti}",
+                    shape.nrows, shape.ncols, nmines
+                ));
+                ui.label(stats.played().to_string());
+                ui.label(stats.won.to_string());
+                ui.label(stats.lost.to_string());
+                ui.label(stats.assisted.to_string());
+                ui.label(format!("{:.0}%", 100.0 * stats.win_rate()));
+                ui.label(fmt_time(stats.best_time()));
+                ui.label(fmt_time(stats.average_time()));
+                ui.label(fmt_time(stats.median_time()));
+                ui.label(stats.current_streak.to_string());
+                ui.label(stats.best_streak.to_string());
+                ui.end_row();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_and_streaks() {
+        let mut stats = ConfigStats::default();
+        stats.record_win(Duration::from_secs(30), false);
+        stats.record_win(Duration::from_secs(10), false);
+        stats.record_win(Duration::from_secs(5), true);
+        stats.record_loss(false);
+        stats.record_win(Duration::from_secs(20), false);
+        assert_eq!(stats.played(), 5);
+        assert_eq!(stats.win_rate(), 0.8);
+        assert_eq!(stats.best_time(), Some(Duration::from_secs(10)));
+        assert_eq!(stats.average_time(), Some(Duration::from_secs(20)));
+        assert_eq!(stats.median_time(), Some(Duration::from_secs(20)));
+        assert_eq!(stats.current_streak, 1);
+        assert_eq!(stats.best_streak, 3);
+    }
+}
@@ -1,11 +1,16 @@
 use eframe::{
     egui::{self, Response},
-    epaint::{self, CircleShape, Color32, FontId, RectShape, Vec2},
+    epaint::{self, CircleShape, Color32, FontId, Pos2, Rect, RectShape, Stroke, Vec2},
 };
 
-use crate::engine::{Cell, CellState};
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq, Eq)]
+use crate::engine::{
+    kernel::{self, Kernel},
+    Cell, CellState,
+};
+
+#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) enum ColorTheme {
     Blue,
     Green,
@@ -18,6 +23,14 @@ struct Colors {
 }
 
 impl ColorTheme {
+    const ALL: [Self; 3] = [Self::Blue, Self::Green, Self::Pink];
+
+    /// Theme following this one in the picker, wrapping around.
+    pub(crate) fn next(&self) -> Self {
+        let i = Self::ALL.iter().position(|t| t == self).unwrap_or(0);
+        Self::ALL[(i + 1) % Self::ALL.len()]
+    }
+
     pub(crate) fn main_color(&self) -> Color32 {
         self.colors().main
     }
@@ -40,8 +53,12 @@ impl ColorTheme {
     }
 
     fn on_response(&self, response: &Response) -> Color32 {
+        self.on_highlight(response.hovered() || response.has_focus())
+    }
+
+    fn on_highlight(&self, highlighted: bool) -> Color32 {
         let colors = self.colors();
-        if response.hovered() || response.has_focus() {
+        if highlighted {
             colors.highlighted
         } else {
             colors.main
@@ -72,7 +89,7 @@ impl egui::Widget for ThemeOption {
 
 pub(crate) fn theme_picker(theme: &mut ColorTheme, ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
-        for ct in [ColorTheme::Blue, ColorTheme::Green, ColorTheme::Pink] {
+        for ct in ColorTheme::ALL {
             if ui
                 .add(ThemeOption {
                     selected: ct == *theme,
@@ -86,10 +103,49 @@ pub(crate) fn theme_picker(theme: &mut ColorTheme, ui: &mut egui::Ui) {
     });
 }
 
+/// Preset selector and editable mask of the neighbourhood counted by clues.
+pub(crate) fn kernel_picker(kernel: &mut Kernel, theme: ColorTheme, ui: &mut egui::Ui) {
+    egui::ComboBox::from_label("Clues")
+        .selected_text(kernel.name().unwrap_or("Custom"))
+        .show_ui(ui, |ui| {
+            for (name, preset) in Kernel::PRESETS {
+                ui.selectable_value(kernel, preset, name);
+            }
+        });
+    let size = ui.spacing().interact_size.y * 0.8;
+    egui::Grid::new("kernel")
+        .min_col_width(0.0)
+        .min_row_height(0.0)
+        .spacing((2.0, 2.0))
+        .show(ui, |ui| {
+            for drow in -kernel::RADIUS..=kernel::RADIUS {
+                for dcol in -kernel::RADIUS..=kernel::RADIUS {
+                    let (rect, response) =
+                        ui.allocate_exact_size(Vec2::splat(size), egui::Sense::click());
+                    let color = if (drow, dcol) == (0, 0) {
+                        Color32::from_gray(200)
+                    } else if kernel.contains(drow, dcol) {
+                        theme.on_response(&response)
+                    } else {
+                        Color32::from_gray(60)
+                    };
+                    ui.painter().rect_filled(rect, 0.0, color);
+                    if response.clicked() {
+                        kernel.toggle(drow, dcol);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+}
+
 pub(crate) struct CellButton {
     cell: CellState,
     scaling: f32,
     theme: ColorTheme,
+    probability: Option<f64>,
+    highlight: Option<Color32>,
+    ghost: bool,
 }
 
 impl CellButton {
@@ -98,19 +154,152 @@ impl CellButton {
             cell,
             scaling,
             theme,
+            probability: None,
+            highlight: None,
+            ghost: false,
         }
     }
 
+    pub(crate) fn with_probability(mut self, probability: Option<f64>) -> Self {
+        self.probability = probability;
+        self
+    }
+
+    pub(crate) fn with_highlight(mut self, highlight: Option<Color32>) -> Self {
+        self.highlight = highlight;
+        self
+    }
+
+    /// Faded copy of a cell shown across the seam of a wrap-around board.
+    pub(crate) fn with_ghost(mut self, ghost: bool) -> Self {
+        self.ghost = ghost;
+        self
+    }
+
     pub(crate) fn base_size(ui: &egui::Ui) -> f32 {
         ui.spacing().interact_size.y * 2.0
     }
 
-    fn fill_color(&self, response: &Response) -> Color32 {
-        match self.cell {
-            CellState::Hidden | CellState::Flagged => self.theme.on_response(response),
-            CellState::Visible(Cell::Mine) => Color32::DARK_RED,
+    fn fade(&self, color: Color32) -> Color32 {
+        if self.ghost {
+            color.gamma_multiply(0.35)
+        } else {
+            color
+        }
+    }
+
+    fn fill_color(&self, highlighted: bool) -> Color32 {
+        let color = match self.cell {
+            CellState::Hidden
+            | CellState::Flagged(_)
+            | CellState::AntiFlagged
+            | CellState::Questioned => self.theme.on_highlight(highlighted),
+            CellState::Visible(Cell::Mine(_)) => Color32::DARK_RED,
+            CellState::Visible(Cell::AntiMine) => Color32::DARK_BLUE,
             CellState::Visible(Cell::Clear) => Color32::TRANSPARENT,
             CellState::Visible(Cell::Neighbouring(_)) => Color32::from_gray(35),
+        };
+        self.fade(color)
+    }
+
+    /// Draw the cell as a square, for boards without a grid of widgets.
+    pub(crate) fn paint_square(&self, painter: &egui::Painter, rect: Rect, highlighted: bool) {
+        self.paint(painter, CellOutline::Square(rect), highlighted);
+    }
+
+    /// Draw the cell as a hexagon, for boards without a grid of widgets.
+    pub(crate) fn paint_hex(
+        &self,
+        painter: &egui::Painter,
+        center: Pos2,
+        radius: f32,
+        highlighted: bool,
+    ) {
+        self.paint(painter, CellOutline::Hexagon(center, radius), highlighted);
+    }
+
+    fn paint(&self, painter: &egui::Painter, outline: CellOutline, highlighted: bool) {
+        let color = self.fill_color(highlighted);
+        let center = outline.center();
+        let shape: epaint::Shape = match (self.cell, outline) {
+            (
+                CellState::Flagged(_)
+                | CellState::AntiFlagged
+                | CellState::Visible(Cell::Mine(_) | Cell::AntiMine),
+                _,
+            ) => CircleShape::filled(center, outline.inner_radius(), color).into(),
+            (_, CellOutline::Square(rect)) => RectShape::filled(rect, 0.0, color).into(),
+            (_, CellOutline::Hexagon(..)) => {
+                epaint::Shape::convex_polygon(outline.vertices(), color, Stroke::NONE)
+            }
+        };
+        painter.add(shape);
+        if let CellState::Flagged(n) | CellState::Visible(Cell::Mine(n)) = self.cell {
+            if n > 1 {
+                painter.text(
+                    center,
+                    egui::Align2::CENTER_CENTER,
+                    n.to_string(),
+                    FontId {
+                        size: 14.0 * self.scaling,
+                        family: epaint::FontFamily::Proportional,
+                    },
+                    self.fade(Color32::from_gray(230)),
+                );
+            }
+        }
+        // Anti-mines are told apart from mines by a minus sign.
+        if let CellState::AntiFlagged | CellState::Visible(Cell::AntiMine) = self.cell {
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                "\u{2212}",
+                FontId {
+                    size: 18.0 * self.scaling,
+                    family: epaint::FontFamily::Proportional,
+                },
+                self.fade(Color32::from_gray(230)),
+            );
+        }
+        if let CellState::Questioned = self.cell {
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                "?",
+                FontId {
+                    size: 18.0 * self.scaling,
+                    family: epaint::FontFamily::Proportional,
+                },
+                self.fade(Color32::from_gray(230)),
+            );
+        }
+        if let CellState::Visible(Cell::Neighbouring(i)) = self.cell {
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                i.to_string(),
+                FontId {
+                    size: 18.0 * self.scaling,
+                    family: epaint::FontFamily::Proportional,
+                },
+                self.fade(self.theme.on_highlight(highlighted)),
+            );
+        }
+        if let (CellState::Hidden, Some(p)) = (self.cell, self.probability) {
+            painter.text(
+                center,
+                egui::Align2::CENTER_CENTER,
+                format!("{:.0}", 100.0 * p),
+                FontId {
+                    size: 10.0 * self.scaling,
+                    family: epaint::FontFamily::Proportional,
+                },
+                self.fade(Color32::from_gray(220)),
+            );
+        }
+        if let Some(color) = self.highlight {
+            let stroke = Stroke::new(3.0 * self.scaling, self.fade(color));
+            painter.add(epaint::Shape::closed_line(outline.vertices(), stroke));
         }
     }
 }
@@ -122,28 +311,117 @@ impl egui::Widget for CellButton {
             egui::Sense::click_and_drag(),
         );
         if ui.is_rect_visible(rect) {
-            let color = self.fill_color(&response);
-            let shape: epaint::Shape = match self.cell {
-                CellState::Flagged | CellState::Visible(Cell::Mine) => {
-                    CircleShape::filled(rect.center(), 0.5 * rect.height(), color).into()
+            let highlighted = response.hovered() || response.has_focus();
+            self.paint(ui.painter(), CellOutline::Square(rect), highlighted);
+        }
+        response
+    }
+}
+
+const SQRT_3: f32 = 1.732_050_8;
+
+#[derive(Copy, Clone)]
+enum CellOutline {
+    Square(Rect),
+    /// Pointy-top hexagon with its center and the radius of its vertices.
+    Hexagon(Pos2, f32),
+}
+
+impl CellOutline {
+    fn center(&self) -> Pos2 {
+        match *self {
+            Self::Square(rect) => rect.center(),
+            Self::Hexagon(center, _) => center,
+        }
+    }
+
+    fn inner_radius(&self) -> f32 {
+        match *self {
+            Self::Square(rect) => 0.5 * rect.height(),
+            Self::Hexagon(_, radius) => 0.5 * SQRT_3 * radius,
+        }
+    }
+
+    fn vertices(&self) -> Vec<Pos2> {
+        match *self {
+            Self::Square(rect) => vec![
+                rect.left_top(),
+                rect.right_top(),
+                rect.right_bottom(),
+                rect.left_bottom(),
+            ],
+            Self::Hexagon(center, radius) => (0..6)
+                .map(|i| {
+                    let angle = std::f32::consts::FRAC_PI_3 * (i as f32 + 0.5);
+                    center + radius * Vec2::angled(angle)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Placement of the cells of a hexagonal board, odd rows being shifted right
+/// by half a cell.
+pub(crate) struct HexLayout {
+    pub(crate) radius: f32,
+    origin: Pos2,
+    nrows: usize,
+    ncols: usize,
+}
+
+impl HexLayout {
+    /// Largest layout that fits in the available size.
+    pub(crate) fn fit(available: Vec2, nrows: usize, ncols: usize) -> Self {
+        let radius_w = available.x / ((ncols as f32 + 0.5) * SQRT_3);
+        let radius_h = available.y / (1.5 * nrows as f32 + 0.5);
+        Self {
+            radius: radius_w.min(radius_h),
+            origin: Pos2::ZERO,
+            nrows,
+            ncols,
+        }
+    }
+
+    pub(crate) fn size(&self) -> Vec2 {
+        Vec2::new(
+            (self.ncols as f32 + 0.5) * SQRT_3 * self.radius,
+            (1.5 * self.nrows as f32 + 0.5) * self.radius,
+        )
+    }
+
+    pub(crate) fn with_origin(self, origin: Pos2) -> Self {
+        Self { origin, ..self }
+    }
+
+    pub(crate) fn center(&self, irow: usize, icol: usize) -> Pos2 {
+        let shift = if irow.is_multiple_of(2) { 0.5 } else { 1.0 };
+        self.origin
+            + Vec2::new(
+                SQRT_3 * self.radius * (icol as f32 + shift),
+                self.radius * (1.0 + 1.5 * irow as f32),
+            )
+    }
+
+    /// Cell under the given position, the one with the nearest center.
+    pub(crate) fn cell_at(&self, pos: Pos2) -> Option<(usize, usize)> {
+        let rel = pos - self.origin;
+        let irow = ((rel.y / self.radius - 1.0) / 1.5).round() as isize;
+        let icol = (rel.x / (SQRT_3 * self.radius) - 0.5).round() as isize;
+        let mut best = None;
+        for ir in irow - 1..=irow + 1 {
+            for ic in icol - 1..=icol + 1 {
+                let (Ok(ir), Ok(ic)) = (usize::try_from(ir), usize::try_from(ic)) else {
+                    continue;
+                };
+                if ir >= self.nrows || ic >= self.ncols {
+                    continue;
+                }
+                let dist = self.center(ir, ic).distance(pos);
+                if dist <= self.radius && best.is_none_or(|(_, d)| dist < d) {
+                    best = Some(((ir, ic), dist));
                 }
-                _ => RectShape::filled(rect, 0.0, color).into(),
-            };
-            let painter = ui.painter();
-            painter.add(shape);
-            if let CellState::Visible(Cell::Neighbouring(i)) = self.cell {
-                painter.text(
-                    rect.center(),
-                    egui::Align2::CENTER_CENTER,
-                    i.to_string(),
-                    FontId {
-                        size: 18.0 * self.scaling,
-                        family: epaint::FontFamily::Proportional,
-                    },
-                    self.theme.on_response(&response),
-                );
             }
         }
-        response
+        best.map(|(cell, _)| cell)
     }
 }
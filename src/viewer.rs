@@ -0,0 +1,102 @@
+use std::time::{Duration, Instant};
+
+use eframe::egui::{self, RichText};
+
+use crate::{
+    engine::{history::History, replay::Replay, Board},
+    format_duration,
+};
+
+/// Playback of a recorded game on the board grid.
+pub(crate) struct ReplayViewer {
+    replay: Replay,
+    board: Board,
+    history: History,
+    n_applied: usize,
+    position: Duration,
+    playing: bool,
+    speed: f32,
+    last_frame: Instant,
+}
+
+impl ReplayViewer {
+    pub(crate) fn new(replay: Replay) -> Self {
+        let board = replay.initial_board();
+        let mut viewer = Self {
+            replay,
+            board,
+            history: History::default(),
+            n_applied: 0,
+            position: Duration::ZERO,
+            playing: true,
+            speed: 1.0,
+            last_frame: Instant::now(),
+        };
+        viewer.seek(Duration::ZERO);
+        viewer
+    }
+
+    pub(crate) fn board(&self) -> &Board {
+        &self.board
+    }
+
+    fn seek(&mut self, position: Duration) {
+        if position < self.position {
+            self.board = self.replay.initial_board();
+            self.history = History::default();
+            self.n_applied = 0;
+        }
+        self.position = position.min(self.replay.duration());
+        let moves = &self.replay.moves()[self.n_applied..];
+        for mv in moves.iter().take_while(|m| m.time <= self.position) {
+            self.history.play(&mut self.board, mv.action);
+            self.n_applied += 1;
+        }
+    }
+
+    /// Move forward by the time elapsed since the last frame.
+    pub(crate) fn advance(&mut self) {
+        let now = Instant::now();
+        let elapsed = now - self.last_frame;
+        self.last_frame = now;
+        if self.playing {
+            self.seek(self.position + elapsed.mul_f32(self.speed));
+            if self.position >= self.replay.duration() {
+                self.playing = false;
+            }
+        }
+    }
+
+    /// Draw the playback controls, return whether the viewer should close.
+    pub(crate) fn controls(&mut self, ui: &mut egui::Ui) -> bool {
+        ui.label(RichText::new("Replay").size(20.0));
+        ui.label(format!(
+            "{} / {}",
+            format_duration(self.position),
+            format_duration(self.replay.duration())
+        ));
+        let mut position = self.position.as_secs_f32();
+        let duration = self.replay.duration().as_secs_f32();
+        let scrubber = egui::Slider::new(&mut position, 0.0..=duration).show_value(false);
+        if ui.add(scrubber).changed() {
+            self.seek(Duration::from_secs_f32(position));
+        }
+        ui.add(
+            egui::Slider::new(&mut self.speed, 0.25..=8.0)
+                .logarithmic(true)
+                .text("Speed"),
+        );
+        let mut close = false;
+        ui.horizontal(|ui| {
+            let label = if self.playing { "Pause" } else { "Play" };
+            if ui.button(label).clicked() {
+                if !self.playing && self.position >= self.replay.duration() {
+                    self.seek(Duration::ZERO);
+                }
+                self.playing = !self.playing;
+            }
+            close = ui.button("Close").clicked();
+        });
+        close
+    }
+}